@@ -16,3 +16,9 @@
 // <https://www.gnu.org/licenses/.
 
 use super::*;
+use rt::{Effect, Symbolic};
+
+mod superoptimize;
+mod synthesize;
+pub use self::superoptimize::{Superoptimizer, Suggestion};
+pub use self::synthesize::{Example, Synthesizer};
@@ -0,0 +1,223 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use std::collections::HashMap;
+
+/// Behaviours of candidates already tried, by depth of the stack they
+/// were tried on. They do not depend on the reference, so one search
+/// over many words can share them.
+type Cache = HashMap<(String, usize), rt::Result<Symbolic>>;
+
+/// The items candidates are built from: the opcodes that reduce, and
+/// blocks holding one of them or nothing.
+pub(crate) const ALPHABET: [&str; 13] = [
+  "a", "b", "c", "d", "e", "f",
+  "[a]", "[b]", "[c]", "[d]", "[e]", "[f]", "[]",
+];
+
+/// Returns true if a candidate ending in `lhs rhs` has a shorter
+/// equivalent, so it can be skipped: `swap swap`, `copy drop`, and a
+/// block that is applied or dropped as soon as it is pushed.
+fn is_redundant(lhs: &str, rhs: &str) -> bool {
+  return (lhs == "f" && rhs == "f") ||
+    (lhs == "d" && rhs == "e") ||
+    (lhs.starts_with('[') && (rhs == "a" || rhs == "e"));
+}
+
+/// Lists every candidate of exactly `length` items from an alphabet,
+/// in order.
+pub(crate) fn candidates<'a>(
+  alphabet: &[&'a str], length: usize) -> Vec<String> {
+  let mut dst: Vec<Vec<&'a str>> = vec![Vec::new()];
+  for _ in 0..length {
+    let mut next = Vec::new();
+    for prefix in dst.iter() {
+      for item in alphabet.iter() {
+        if let Some(last) = prefix.last() {
+          if is_redundant(last, item) {
+            continue;
+          }
+        }
+        let mut candidate = prefix.clone();
+        candidate.push(item);
+        next.push(candidate);
+      }
+    }
+    dst = next;
+  }
+  return dst.into_iter().map(|x| x.join(" ")).collect();
+}
+
+/// Counts the atoms and blocks of a term, the measure a shorter
+/// definition is judged by.
+pub(crate) fn size(src: &str) -> usize {
+  let spaced = src.replace('[', " [ ").replace(']', " ");
+  return spaced.split_whitespace().count();
+}
+
+/// A shorter body found for a library word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+  pub key: String,
+  /// The word's body with library words inlined.
+  pub current: String,
+  pub shorter: String,
+}
+
+/// Searches opcode sequences, shortest first, for one that behaves
+/// like a reference term or has a given stack effect.
+pub struct Superoptimizer {
+  pub max_length: usize,
+  pub time_quota: u64,
+}
+
+impl Superoptimizer {
+  pub fn new(max_length: usize, time_quota: u64) -> Self {
+    Superoptimizer {
+      max_length: max_length,
+      time_quota: time_quota,
+    }
+  }
+
+  /// Reduces a term over unknown inputs, inlining library words in the
+  /// result so that equal behaviour gives equal text. The number of
+  /// inputs is the term's own unless `inputs` is given.
+  fn behaviour(
+    &self,
+    pod: &mut Pod,
+    src: &str,
+    inputs: Option<usize>) -> rt::Result<Symbolic> {
+    let mut dst = match inputs {
+      Some(inputs) => pod.symbolic_over(src, inputs, self.time_quota)?,
+      None => pod.symbolic(src, self.time_quota)?,
+    };
+    dst.output = pod.optimize_term(&dst.output)?;
+    return Ok(dst);
+  }
+
+  /// Returns true if a candidate behaves as expected on the stack the
+  /// expected behaviour was found on. A candidate that needs more
+  /// inputs gets stuck there, and so leaves a different result.
+  fn is_match(
+    &self,
+    pod: &mut Pod,
+    candidate: &str,
+    expected: &Symbolic,
+    cache: &mut Cache) -> bool {
+    let inputs = expected.inputs.len();
+    let key = (candidate.to_string(), inputs);
+    if !cache.contains_key(&key) {
+      let found = self.behaviour(pod, candidate, Some(inputs));
+      cache.insert(key.clone(), found);
+    }
+    return cache[&key].as_ref() == Ok(expected);
+  }
+
+  /// Finds the shortest opcode sequence that behaves like `reference`,
+  /// as checked by symbolic evaluation, if one is short enough.
+  pub fn for_term(
+    &self, pod: &mut Pod, reference: &str) -> rt::Result<Option<String>> {
+    return self.search(pod, reference, self.max_length, &mut HashMap::new());
+  }
+
+  fn search(
+    &self,
+    pod: &mut Pod,
+    reference: &str,
+    max_length: usize,
+    cache: &mut Cache) -> rt::Result<Option<String>> {
+    let expected = self.behaviour(pod, reference, None)?;
+    for length in 0..max_length + 1 {
+      for candidate in candidates(&ALPHABET, length).into_iter() {
+        if self.is_match(pod, &candidate, &expected, cache) {
+          return Ok(Some(candidate));
+        }
+      }
+    }
+    return Ok(None);
+  }
+
+  /// Finds the shortest opcode sequence with a given stack effect, if
+  /// one is short enough.
+  pub fn for_effect(
+    &self, pod: &mut Pod, effect: &Effect) -> rt::Result<Option<String>> {
+    let expected = effect.to_string();
+    for length in 0..self.max_length + 1 {
+      for candidate in candidates(&ALPHABET, length).into_iter() {
+        if let Ok(found) = pod.infer(&candidate) {
+          if found.to_string() == expected {
+            return Ok(Some(candidate));
+          }
+        }
+      }
+    }
+    return Ok(None);
+  }
+
+  /// Looks for a shorter body for every word of the library. Words
+  /// whose bodies hold hints or literals are left alone, since inputs
+  /// that are always blocks would pass over what those check.
+  pub fn suggest(&self, pod: &mut Pod) -> rt::Result<Vec<Suggestion>> {
+    let mut dst = Vec::new();
+    let mut cache = HashMap::new();
+    for key in pod.keys().iter() {
+      let current = pod.optimize_term(key)?;
+      let is_pure = !current.contains(|x| "(\"0123456789".contains(x));
+      if !is_pure || size(&current) <= 1 {
+        continue;
+      }
+      let max_length = self.max_length.min(size(&current) - 1);
+      let shorter = match self.search(pod, key, max_length, &mut cache) {
+        Ok(Some(shorter)) => shorter,
+        _ => {
+          continue;
+        }
+      };
+      if size(&shorter) < size(&current) {
+        dst.push(Suggestion {
+          key: key.to_string(),
+          current: current,
+          shorter: shorter,
+        });
+      }
+    }
+    return Ok(dst);
+  }
+}
+
+#[test]
+fn superoptimize() {
+  let src = "```
+:swap f
+:drop e
+:nip swap drop
+:rot [f] f b c a
+:long d e f f b [e] a
+```
+";
+  let mut pod = Pod::from_string(src, 1 << 16, 1 << 16).unwrap();
+  let search = Superoptimizer::new(2, 1 << 12);
+  assert_eq!(search.for_term(&mut pod, "[f] a"), Ok(Some("f".to_string())));
+  assert_eq!(search.for_term(&mut pod, "nip"), Ok(Some("f e".to_string())));
+  assert_eq!(search.for_term(&mut pod, "long"), Ok(Some("e".to_string())));
+  let effect = pod.infer("drop drop").unwrap();
+  assert_eq!(search.for_effect(&mut pod, &effect), Ok(Some("e e".to_string())));
+  let suggestions = search.suggest(&mut pod).unwrap();
+  let keys: Vec<&str> = suggestions.iter().map(|x| &*x.key).collect();
+  assert_eq!(keys, vec!["long"]);
+}
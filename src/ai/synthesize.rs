@@ -0,0 +1,129 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use super::superoptimize::{candidates, size, ALPHABET};
+
+/// An example a synthesized term must satisfy: run on the stack
+/// `input`, it leaves `output`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+  pub input: String,
+  pub output: String,
+}
+
+impl Example {
+  /// Reads an example written like a test, as `input = output`. Both
+  /// sides must be terms rather than statements.
+  pub fn parse(src: &str) -> rt::Result<Self> {
+    let (input, output) = rt::split_test(src).ok_or(rt::Error::Syntax)?;
+    if rt::is_statement(input) || rt::is_statement(output) {
+      return Err(rt::Error::Syntax);
+    }
+    return Ok(Example {
+      input: input.trim().to_string(),
+      output: output.trim().to_string(),
+    });
+  }
+}
+
+/// Searches terms built from opcodes, singleton blocks and library
+/// words for those that satisfy every example.
+pub struct Synthesizer {
+  pub max_length: usize,
+  /// Steps allowed for the whole search.
+  pub time_quota: u64,
+}
+
+impl Synthesizer {
+  pub fn new(max_length: usize, time_quota: u64) -> Self {
+    Synthesizer {
+      max_length: max_length,
+      time_quota: time_quota,
+    }
+  }
+
+  /// Returns every term of up to `max_length` items that satisfies the
+  /// examples, smallest first. The search stops early, keeping what it
+  /// has found, once it has spent the time quota. The library is left
+  /// as it was.
+  pub fn synthesize(
+    &self, pod: &mut Pod, examples: &[Example]) -> rt::Result<Vec<String>> {
+    let mut quota = self.time_quota;
+    let mut expected = Vec::new();
+    for example in examples.iter() {
+      expected.push(pod.reduce_term(&example.output, &mut quota)?);
+    }
+    let keys = pod.keys();
+    let mut alphabet: Vec<&str> = ALPHABET.to_vec();
+    alphabet.extend(keys.iter().map(|x| &**x));
+    let mut dst = Vec::new();
+    'search: for length in 0..self.max_length + 1 {
+      for candidate in candidates(&alphabet, length).into_iter() {
+        let mut is_match = true;
+        for (example, expected) in examples.iter().zip(expected.iter()) {
+          if quota == 0 {
+            break 'search;
+          }
+          let src = format!("{} {}", example.input, candidate);
+          let found = pod.reduce_term(&src, &mut quota);
+          if found.as_ref() != Ok(expected) {
+            is_match = false;
+            break;
+          }
+        }
+        if is_match {
+          dst.push(candidate);
+        }
+      }
+    }
+    dst.sort_by_key(|x| (size(x), x.clone()));
+    return Ok(dst);
+  }
+}
+
+#[test]
+fn synthesize() {
+  let src = "```
+:swap f
+:pair b f b f c
+```
+";
+  let mut pod = Pod::from_string(src, 1 << 16, 1 << 16).unwrap();
+  let examples: Vec<Example> = [
+    "[A] [B] = [B] [A]",
+    "[C] [D] [E] = [C] [E] [D]",
+  ].iter().map(|x| Example::parse(x).unwrap()).collect();
+  let search = Synthesizer::new(2, 1 << 20);
+  let dst = search.synthesize(&mut pod, &examples).unwrap();
+  assert_eq!(&dst[..2], &["f".to_string(), "swap".to_string()]);
+  let examples = vec![Example::parse("[A] [B] = [[B] [A]]").unwrap()];
+  let dst = search.synthesize(&mut pod, &examples).unwrap();
+  assert_eq!(dst[0], "f pair");
+  assert_eq!(Example::parse("[A]"), Err(rt::Error::Syntax));
+  assert_eq!(Example::parse(":x [A] = [A]"), Err(rt::Error::Syntax));
+  assert_eq!(Example::parse("[A] = ~swap"), Err(rt::Error::Syntax));
+  let before = pod.to_string().unwrap();
+  let examples = vec![Example {
+    input: "+r x => [B]".to_string(),
+    output: String::new(),
+  }];
+  assert_eq!(search.synthesize(&mut pod, &examples), Ok(vec![]));
+  assert_eq!(pod.to_string().unwrap(), before);
+  let search = Synthesizer::new(2, 8);
+  assert_eq!(search.synthesize(&mut pod, &examples), Ok(vec![]));
+}
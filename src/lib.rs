@@ -15,20 +15,34 @@
 // License along with this program.  If not, see
 // <https://www.gnu.org/licenses/.
 
+// The house style spells out returns, field initializers, reference
+// patterns and predicates written as matches; keep clippy from
+// fighting it.
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::match_ref_pats)]
+#![allow(clippy::needless_borrowed_reference)]
+#![allow(clippy::match_like_matches_macro)]
+
 #[macro_use]
 extern crate lazy_static;
 extern crate regex;
 extern crate wasm_bindgen;
+extern crate serde_json;
 
 use wasm_bindgen::prelude::*;
 
 pub mod rt;
+pub mod lsp;
+pub mod ai;
 pub use self::rt::Pod;
 
+#[allow(dead_code)]
 struct Database {
 
 }
 
+#[allow(dead_code)]
 impl Database {
   fn new() -> Self {
     Database {
@@ -47,6 +61,7 @@ lazy_static! {
 }
 
 #[wasm_bindgen]
+#[allow(unused_variables)]
 pub fn exec(uid: i64, src: &str) -> String {
   return src.to_string();
 }
@@ -0,0 +1,443 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use serde_json::{json, Value};
+use crate::rt::{self, Error, Pod};
+
+/// A problem found in a document, on one line.
+#[derive(Debug, Clone)]
+struct Diagnostic {
+  line: usize,
+  /// Byte columns of the span within the line.
+  start: usize,
+  end: usize,
+  message: String,
+}
+
+/// An open pod file, loaded statement by statement.
+struct Document {
+  text: String,
+  pod: Pod,
+  /// The line each key is defined on, with the length of its head.
+  definitions: HashMap<String, (usize, usize)>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+/// A language server for pod files. Each open document is loaded
+/// into its own pod, so hovers show definitions as the library holds
+/// them: reduced.
+pub struct Server {
+  documents: HashMap<String, Document>,
+  space_quota: usize,
+  time_quota: u64,
+  is_exit: bool,
+}
+
+fn describe(error: &Error) -> String {
+  match error {
+    &Error::Syntax => "syntax error".to_string(),
+    &Error::Time => "ran out of time".to_string(),
+    &Error::Space => "ran out of space".to_string(),
+    &Error::Assert(ref message) => format!("assertion failed: {}", message),
+    _ => format!("{:?}", error),
+  }
+}
+
+/// Converts a statement offset into a line and byte column, relative
+/// to the statement's first line.
+fn locate(src: &str, offset: usize) -> (usize, usize) {
+  let before = &src[..offset];
+  let line = before.matches('\n').count();
+  let column = before.rfind('\n').map(|x| offset - x - 1).unwrap_or(offset);
+  return (line, column);
+}
+
+/// Converts a byte column into a UTF-16 column, as LSP counts them.
+fn to_character(line: &str, column: usize) -> usize {
+  let column = column.min(line.len());
+  return line[..column].encode_utf16().count();
+}
+
+/// Converts a UTF-16 column into a byte column.
+fn to_column(line: &str, character: usize) -> usize {
+  let mut count = 0;
+  for (index, ch) in line.char_indices() {
+    if count >= character {
+      return index;
+    }
+    count += ch.len_utf16();
+  }
+  return line.len();
+}
+
+fn is_word_char(ch: char) -> bool {
+  return ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-';
+}
+
+impl Document {
+  fn new(text: String, space_quota: usize, time_quota: u64) -> Self {
+    let pod = Pod::from_string("", space_quota, time_quota)
+      .expect("empty pod");
+    let mut document = Document {
+      text: text,
+      pod: pod,
+      definitions: HashMap::new(),
+      diagnostics: Vec::new(),
+    };
+    document.analyze(time_quota);
+    return document;
+  }
+
+  /// Loads each statement, reporting the ones that fail, then reports
+  /// the words left undefined once the whole file is loaded.
+  fn analyze(&mut self, time_quota: u64) {
+    let statements = rt::statements(&self.text);
+    for statement in statements.iter() {
+      if statement.is_test {
+        continue;
+      }
+      let source = &statement.source;
+      if source.starts_with(':') {
+        let head = source.split_whitespace().next().unwrap_or(source);
+        let key = head[1..].trim_end_matches('!');
+        let key = key.split('/').next().unwrap_or(key).to_string();
+        self.definitions.insert(key, (statement.line, head.len()));
+      }
+      if let Err(error) = self.pod.eval(source, time_quota) {
+        let end = source.find('\n').unwrap_or(source.len());
+        let diagnostic = Diagnostic {
+          line: statement.line,
+          start: 0,
+          end: end,
+          message: describe(&error),
+        };
+        self.diagnostics.push(diagnostic);
+      }
+    }
+    for statement in statements.iter() {
+      let words = match rt::words(&statement.source) {
+        Ok(words) => words,
+        Err(_) => {
+          if statement.is_test {
+            let diagnostic = Diagnostic {
+              line: statement.line,
+              start: 0,
+              end: statement.source.find('\n')
+                .unwrap_or(statement.source.len()),
+              message: describe(&Error::Syntax),
+            };
+            self.diagnostics.push(diagnostic);
+          }
+          continue;
+        }
+      };
+      for &(offset, word) in words.iter() {
+        // Words that cannot be keys, like `A`, are inert placeholders.
+        if !word.chars().all(is_word_char) || self.pod.is_defined(word) {
+          continue;
+        }
+        let (line, column) = locate(&statement.source, offset);
+        let diagnostic = Diagnostic {
+          line: statement.line + line,
+          start: column,
+          end: column + word.len(),
+          message: format!("undefined word `{}`", word),
+        };
+        self.diagnostics.push(diagnostic);
+      }
+    }
+  }
+
+  fn line(&self, line: usize) -> &str {
+    return self.text.lines().nth(line).unwrap_or("");
+  }
+
+  fn range(&self, line: usize, start: usize, end: usize) -> Value {
+    let text = self.line(line);
+    return json!({
+      "start": { "line": line, "character": to_character(text, start) },
+      "end": { "line": line, "character": to_character(text, end) },
+    });
+  }
+
+  /// Finds the word under an LSP position.
+  fn word_at(&self, position: &Value) -> Option<String> {
+    let line = position["line"].as_u64()? as usize;
+    let text = self.line(line);
+    let column = to_column(text, position["character"].as_u64()? as usize);
+    let start = text[..column].rfind(|x| !is_word_char(x))
+      .map(|x| x + 1).unwrap_or(0);
+    let end = text[column..].find(|x| !is_word_char(x))
+      .map(|x| x + column).unwrap_or(text.len());
+    if start >= end {
+      return None;
+    }
+    return Some(text[start..end].to_string());
+  }
+}
+
+impl Server {
+  pub fn new(space_quota: usize, time_quota: u64) -> Self {
+    Server {
+      documents: HashMap::new(),
+      space_quota: space_quota,
+      time_quota: time_quota,
+      is_exit: false,
+    }
+  }
+
+  /// Answers messages from a client until it asks the server to exit
+  /// or closes the stream.
+  pub fn serve<R, W>(&mut self, input: &mut R, output: &mut W) -> io::Result<()>
+    where R: BufRead, W: Write {
+    while !self.is_exit {
+      let body = match read_message(input)? {
+        Some(body) => body,
+        None => {
+          return Ok(());
+        }
+      };
+      let replies = match serde_json::from_str(&body) {
+        Ok(message) => self.handle(&message),
+        Err(_) => {
+          vec![failure(Value::Null, -32700, "parse error")]
+        }
+      };
+      for reply in replies.iter() {
+        write_message(output, reply)?;
+      }
+    }
+    return Ok(());
+  }
+
+  /// Handles one message, returning the responses and notifications
+  /// to send back.
+  pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+    let method = message["method"].as_str().unwrap_or("");
+    let params = &message["params"];
+    let id = message.get("id").cloned();
+    let result = match method {
+      "initialize" => {
+        json!({
+          "capabilities": {
+            "textDocumentSync": 1,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "completionProvider": {},
+          },
+          "serverInfo": { "name": "sundial" },
+        })
+      }
+      "shutdown" => Value::Null,
+      "exit" => {
+        self.is_exit = true;
+        return Vec::new();
+      }
+      "textDocument/didOpen" => {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let text = params["textDocument"]["text"].as_str().unwrap_or("");
+        return self.open(uri, text.to_string());
+      }
+      "textDocument/didChange" => {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let changes = params["contentChanges"].as_array();
+        let text = changes.and_then(|x| x.last())
+          .and_then(|x| x["text"].as_str()).unwrap_or("");
+        return self.open(uri, text.to_string());
+      }
+      "textDocument/didClose" => {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        self.documents.remove(uri);
+        return vec![publish(uri, Vec::new())];
+      }
+      "textDocument/hover" => self.hover(params),
+      "textDocument/definition" => self.definition(params),
+      "textDocument/completion" => self.completion(params),
+      _ => {
+        return match id {
+          Some(id) => vec![failure(id, -32601, "method not found")],
+          None => Vec::new(),
+        };
+      }
+    };
+    return match id {
+      Some(id) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+      None => Vec::new(),
+    };
+  }
+
+  /// Loads a document's new text and publishes its diagnostics.
+  fn open(&mut self, uri: &str, text: String) -> Vec<Value> {
+    let document = Document::new(text, self.space_quota, self.time_quota);
+    let diagnostics = document.diagnostics.iter().map(|x| {
+      json!({
+        "range": document.range(x.line, x.start, x.end),
+        "severity": 1,
+        "source": "sundial",
+        "message": x.message,
+      })
+    }).collect();
+    self.documents.insert(uri.to_string(), document);
+    return vec![publish(uri, diagnostics)];
+  }
+
+  fn document(&self, params: &Value) -> Option<&Document> {
+    let uri = params["textDocument"]["uri"].as_str()?;
+    return self.documents.get(uri);
+  }
+
+  /// Shows the reduced definition of the word under the cursor.
+  fn hover(&self, params: &Value) -> Value {
+    let lookup = || -> Option<Value> {
+      let document = self.document(params)?;
+      let key = document.word_at(&params["position"])?;
+      let value = document.pod.lookup(&key)?;
+      return Some(json!({
+        "contents": {
+          "kind": "markdown",
+          "value": format!("```\n:{} {}\n```", key, value),
+        },
+      }));
+    };
+    return lookup().unwrap_or(Value::Null);
+  }
+
+  /// Finds the statement defining the word under the cursor.
+  fn definition(&self, params: &Value) -> Value {
+    let lookup = || -> Option<Value> {
+      let document = self.document(params)?;
+      let key = document.word_at(&params["position"])?;
+      let &(line, length) = document.definitions.get(&key)?;
+      return Some(json!({
+        "uri": params["textDocument"]["uri"],
+        "range": document.range(line, 0, length),
+      }));
+    };
+    return lookup().unwrap_or(Value::Null);
+  }
+
+  /// Offers every key in the document's library.
+  fn completion(&self, params: &Value) -> Value {
+    let document = match self.document(params) {
+      Some(document) => document,
+      None => {
+        return json!([]);
+      }
+    };
+    let items: Vec<Value> = document.pod.keys().iter().map(|key| {
+      json!({
+        "label": &**key,
+        "kind": 3,
+        "detail": document.pod.lookup(key).unwrap_or_default(),
+      })
+    }).collect();
+    return Value::Array(items);
+  }
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+  return json!({
+    "jsonrpc": "2.0",
+    "method": "textDocument/publishDiagnostics",
+    "params": { "uri": uri, "diagnostics": diagnostics },
+  });
+}
+
+fn failure(id: Value, code: i64, message: &str) -> Value {
+  return json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "error": { "code": code, "message": message },
+  });
+}
+
+/// Reads one message framed by a `Content-Length` header.
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some(value) = line.strip_prefix("Content-Length:") {
+      length = value.trim().parse::<usize>().ok();
+    }
+  }
+  let length = length.ok_or_else(|| {
+    io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length")
+  })?;
+  let mut body = vec![0; length];
+  input.read_exact(&mut body)?;
+  return String::from_utf8(body).map(Some).map_err(|error| {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+  });
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+  let body = message.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  return output.flush();
+}
+
+#[test]
+fn server() {
+  let mut server = Server::new(4096, 4096);
+  let uri = "file:///pod.md";
+  let text = concat!(
+    "# Pod\n\n```\n:pair b f b f c\n:oops pair ]\n:dup2 frob\n```\n\n",
+    "```test\n[A] [B] pair = [[A] [B]]\n```\n");
+  let open = json!({
+    "jsonrpc": "2.0",
+    "method": "textDocument/didOpen",
+    "params": { "textDocument": { "uri": uri, "text": text } },
+  });
+  let replies = server.handle(&open);
+  let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+  let messages: Vec<&str> = diagnostics.iter()
+    .map(|x| x["message"].as_str().unwrap()).collect();
+  assert_eq!(messages, vec!["syntax error", "undefined word `frob`"]);
+  assert_eq!(diagnostics[1]["range"]["start"],
+             json!({ "line": 5, "character": 6 }));
+  let at = |method: &str, line: usize, character: usize| {
+    json!({
+      "jsonrpc": "2.0",
+      "id": 1,
+      "method": method,
+      "params": {
+        "textDocument": { "uri": uri },
+        "position": { "line": line, "character": character },
+      },
+    })
+  };
+  let hover = server.handle(&at("textDocument/hover", 9, 9));
+  assert_eq!(hover[0]["result"]["contents"]["value"],
+             "```\n:pair b f b f c\n```");
+  let definition = server.handle(&at("textDocument/definition", 9, 9));
+  assert_eq!(definition[0]["result"]["range"]["start"]["line"], 3);
+  let completion = server.handle(&at("textDocument/completion", 0, 0));
+  let labels: Vec<&str> = completion[0]["result"].as_array().unwrap()
+    .iter().map(|x| x["label"].as_str().unwrap()).collect();
+  assert_eq!(labels, vec!["dup2", "pair"]);
+  let unknown = server.handle(&at("textDocument/rename", 0, 0));
+  assert_eq!(unknown[0]["error"]["code"], -32601);
+}
@@ -0,0 +1,510 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+//! The `sundial` command line: a REPL over a pod, commands that run,
+//! test or format pod files, and a language server for editing them.
+
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate sundial;
+
+use std::io::BufRead;
+use std::io::Write;
+use sundial::Pod;
+use sundial::rt;
+use sundial::lsp::Server;
+use sundial::ai::{Example, Superoptimizer, Synthesizer};
+
+const USAGE: &str = "\
+usage: sundial [options] [FILE]        start a REPL, loading FILE
+       sundial [options] run FILE      evaluate FILE and print the results
+       sundial [options] test FILE     run the tests declared in FILE
+       sundial [options] fmt FILE      format the code blocks of FILE
+       sundial [options] graph FILE    report how the words of FILE depend
+       sundial [options] types FILE    infer the stack effects of FILE
+       sundial [options] suggest FILE  search for shorter definitions
+       sundial [options] lsp           serve LSP over stdin and stdout
+
+options:
+  --space N    heap capacity in objects (default 65536)
+  --time N     steps allowed per evaluation (default 65536)
+  --width N    line width for fmt (default 72)
+  --check      make fmt report unformatted files instead of fixing them
+  --strict     refuse to redefine or delete words others depend on,
+               unless forced with `:key! ...` or `~key!`
+
+Without FILE the REPL loads $SUNDIAL_HOME/pod/default.md, if any.";
+
+const HELP: &str = "\
+:name term     define a word
+~name          delete a word
++name P => R   rewrite values matching P, ending in a word, to R
+~+name         delete a rewrite rule
+,save PATH     write the pod to PATH
+,load PATH     evaluate the pod file at PATH
+,history       list previous inputs
+,undo          undo the last definition or deletion
+,redo          redo the last undone change
+,revision [N]  show the current revision, or the library as of N
+,symbolic TERM reduce a term over unknown inputs $1, $2, ...
+,optimize TERM inline library words in a term and simplify it
+,synthesize IN = OUT; ...
+               search for terms that turn each IN into its OUT
+,prove H; ... :- GOAL
+               search for a derivation of GOAL from hypotheses H
+,help          show this message
+,quit          leave the REPL
+Anything else is reduced and printed. Input continues onto the next
+line while brackets are open.";
+
+struct Options {
+  space_quota: usize,
+  time_quota: u64,
+  width: usize,
+  is_check: bool,
+  is_strict: bool,
+  command: Option<String>,
+  path: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Option<Options> {
+  let mut options = Options {
+    space_quota: 65536,
+    time_quota: 65536,
+    width: 72,
+    is_check: false,
+    is_strict: false,
+    command: None,
+    path: None,
+  };
+  let mut rest = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--space" => {
+        options.space_quota = args.next()?.parse().ok()?;
+      }
+      "--time" => {
+        options.time_quota = args.next()?.parse().ok()?;
+      }
+      "--width" => {
+        options.width = args.next()?.parse().ok()?;
+      }
+      "--check" => {
+        options.is_check = true;
+      }
+      "--strict" => {
+        options.is_strict = true;
+      }
+      _ if arg.starts_with("--") => {
+        return None;
+      }
+      _ => {
+        rest.push(arg.clone());
+      }
+    }
+  }
+  match rest.len() {
+    0 => {
+      //
+    }
+    1 if rest[0] == "lsp" => {
+      options.command = Some(rest[0].clone());
+    }
+    1 => {
+      options.path = Some(rest[0].clone());
+    }
+    2 => {
+      options.command = Some(rest[0].clone());
+      options.path = Some(rest[1].clone());
+    }
+    _ => {
+      return None;
+    }
+  }
+  return Some(options);
+}
+
+/// Writes to stdout. A reader that has gone away, as when the output
+/// is piped to `head`, ends the program quietly instead of panicking.
+fn emit(text: &str) -> Result<(), String> {
+  let stdout = std::io::stdout();
+  let mut stdout = stdout.lock();
+  match stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush()) {
+    Ok(()) => {
+      return Ok(());
+    }
+    Err(ref error) if error.kind() == std::io::ErrorKind::BrokenPipe => {
+      std::process::exit(0);
+    }
+    Err(error) => {
+      return Err(error.to_string());
+    }
+  }
+}
+
+fn read_pod(path: &str, options: &Options) -> Result<(Pod, Vec<String>), String> {
+  let src = std::fs::read_to_string(path)
+    .map_err(|error| format!("{}: {}", path, error))?;
+  let mut pod = Pod::from_string("", options.space_quota, options.time_quota)
+    .map_err(|error| format!("{:?}", error))?;
+  pod.set_strict(options.is_strict);
+  let dst = pod.load(&src, options.time_quota)
+    .map_err(|error| format!("{}: {:?}", path, error))?;
+  pod.clear_history();
+  return Ok((pod, dst));
+}
+
+fn run(options: &Options) -> Result<(), String> {
+  let path = options.path.as_ref().expect("path");
+  let (_, dst) = read_pod(path, options)?;
+  for line in dst.iter() {
+    emit(&format!("{}\n", line))?;
+  }
+  return Ok(());
+}
+
+fn test(options: &Options) -> Result<(), String> {
+  let path = options.path.as_ref().expect("path");
+  let (mut pod, _) = read_pod(path, options)?;
+  let reports = pod.test(options.time_quota);
+  let mut failures = 0;
+  for report in reports.iter() {
+    if !report.is_pass() {
+      failures += 1;
+      emit(&report.diff())?;
+    }
+  }
+  let passed = reports.len() - failures;
+  emit(&format!("{} passed, {} failed\n", passed, failures))?;
+  if failures > 0 {
+    return Err(format!("{}: {} tests failed", path, failures));
+  }
+  return Ok(());
+}
+
+fn graph(options: &Options) -> Result<(), String> {
+  let path = options.path.as_ref().expect("path");
+  let (pod, _) = read_pod(path, options)?;
+  let graph = pod.graph().map_err(|error| format!("{:?}", error))?;
+  return emit(&graph.report());
+}
+
+fn types(options: &Options) -> Result<(), String> {
+  let path = options.path.as_ref().expect("path");
+  let (pod, _) = read_pod(path, options)?;
+  let mut failures = 0;
+  for signature in pod.signatures().iter() {
+    if !signature.is_consistent() {
+      failures += 1;
+    }
+    emit(&format!("{}\n", signature.describe()))?;
+  }
+  if failures > 0 {
+    return Err(format!("{}: {} inconsistent definitions", path, failures));
+  }
+  return Ok(());
+}
+
+/// The longest opcode sequence `suggest` tries.
+const SUGGEST_LENGTH: usize = 3;
+
+/// How many rules deep the REPL's `,prove` searches.
+const PROVE_DEPTH: usize = 6;
+
+fn prove(pod: &mut Pod, src: &str, time_quota: u64) -> Result<(), String> {
+  let (context, goal) = match src.find(":-") {
+    Some(index) => (&src[..index], &src[index + 2..]),
+    None => ("", src),
+  };
+  let context: Vec<&str> = context.split(';')
+    .map(|x| x.trim()).filter(|x| !x.is_empty()).collect();
+  let found = pod.prove(&context, goal, PROVE_DEPTH, time_quota)
+    .map_err(|error| format!("{:?}", error))?;
+  match found {
+    Some(proof) => {
+      emit(&proof.to_string())?;
+    }
+    None => {
+      emit("[prove] no derivation found\n")?;
+    }
+  }
+  return Ok(());
+}
+
+/// The longest term the REPL's `,synthesize` tries, and how many of
+/// the terms it finds are shown.
+const SYNTHESIZE_LENGTH: usize = 2;
+const SYNTHESIZE_SHOWN: usize = 8;
+
+fn synthesize(
+  pod: &mut Pod, src: &str, time_quota: u64) -> Result<(), String> {
+  let mut examples = Vec::new();
+  for example in src.split(';') {
+    examples.push(Example::parse(example)
+      .map_err(|error| format!("{:?}", error))?);
+  }
+  let search = Synthesizer::new(SYNTHESIZE_LENGTH, time_quota);
+  let found = search.synthesize(pod, &examples)
+    .map_err(|error| format!("{:?}", error))?;
+  if found.is_empty() {
+    emit("[synthesize] nothing found\n")?;
+  }
+  for term in found.iter().take(SYNTHESIZE_SHOWN) {
+    emit(&format!("{}\n", term))?;
+  }
+  return Ok(());
+}
+
+fn suggest(options: &Options) -> Result<(), String> {
+  let path = options.path.as_ref().expect("path");
+  let (mut pod, _) = read_pod(path, options)?;
+  let search = Superoptimizer::new(SUGGEST_LENGTH, options.time_quota);
+  let suggestions = search.suggest(&mut pod)
+    .map_err(|error| format!("{:?}", error))?;
+  for suggestion in suggestions.iter() {
+    emit(&format!("{}: {} => {}\n",
+                  suggestion.key, suggestion.current, suggestion.shorter))?;
+  }
+  return Ok(());
+}
+
+fn fmt(options: &Options) -> Result<(), String> {
+  let path = options.path.as_ref().expect("path");
+  let src = std::fs::read_to_string(path)
+    .map_err(|error| format!("{}: {}", path, error))?;
+  let dst = rt::format_pod(&src, options.width)
+    .map_err(|error| format!("{}: {:?}", path, error))?;
+  if src == dst {
+    return Ok(());
+  }
+  if options.is_check {
+    return Err(format!("{}: not formatted", path));
+  }
+  return std::fs::write(path, dst)
+    .map_err(|error| format!("{}: {}", path, error));
+}
+
+fn lsp(options: &Options) -> Result<(), String> {
+  let mut server = Server::new(options.space_quota, options.time_quota);
+  let stdin = std::io::stdin();
+  let stdout = std::io::stdout();
+  return server.serve(&mut stdin.lock(), &mut stdout.lock())
+    .map_err(|error| error.to_string());
+}
+
+fn repl(options: &Options) -> Result<(), String> {
+  let mut pod = match options.path {
+    Some(ref path) => {
+      read_pod(path, options)?.0
+    }
+    None => {
+      match Pod::default(options.space_quota, options.time_quota) {
+        Ok(pod) => pod,
+        Err(rt::Error::Home) => {
+          Pod::from_string("", options.space_quota, options.time_quota)
+            .map_err(|error| format!("{:?}", error))?
+        }
+        Err(error) => {
+          return Err(format!("default pod: {:?}", error));
+        }
+      }
+    }
+  };
+  pod.set_strict(options.is_strict);
+  let stdin = std::io::stdin();
+  let mut lines = stdin.lock().lines();
+  let mut history: Vec<String> = Vec::new();
+  loop {
+    let mut src = String::new();
+    let mut prompt = "> ";
+    loop {
+      emit(prompt)?;
+      match lines.next() {
+        Some(line) => {
+          let line = line.map_err(|error| error.to_string())?;
+          if !src.is_empty() {
+            src.push('\n');
+          }
+          src.push_str(&line);
+        }
+        None => {
+          emit("\n")?;
+          return Ok(());
+        }
+      }
+      if rt::is_balanced(&src) {
+        break;
+      }
+      prompt = ". ";
+    }
+    let src = src.trim();
+    if src.is_empty() {
+      continue;
+    }
+    history.push(src.to_string());
+    let mut words = src.splitn(2, char::is_whitespace);
+    let command = words.next().unwrap_or("");
+    let arg = words.next().map(|x| x.trim()).unwrap_or("");
+    match command {
+      ",quit" => {
+        return Ok(());
+      }
+      ",help" => {
+        emit(&format!("{}\n", HELP))?;
+      }
+      ",history" => {
+        for (index, line) in history.iter().enumerate() {
+          emit(&format!("{:4} {}\n", index + 1, line))?;
+        }
+      }
+      ",undo" if arg.is_empty() => {
+        if !pod.undo() {
+          emit("error: nothing to undo\n")?;
+        }
+        emit(&format!("[revision] {}\n", pod.revision()))?;
+      }
+      ",redo" if arg.is_empty() => {
+        if !pod.redo() {
+          emit("error: nothing to redo\n")?;
+        }
+        emit(&format!("[revision] {}\n", pod.revision()))?;
+      }
+      ",revision" if arg.is_empty() => {
+        let revisions: Vec<String> = pod.revisions().iter()
+          .map(|x| x.to_string()).collect();
+        let revisions = revisions.join(" ");
+        emit(&format!("[revision] {} of {}\n", pod.revision(), revisions))?;
+      }
+      ",revision" => {
+        let result = arg.parse().map_err(|_| rt::Error::Syntax)
+          .and_then(|number| pod.show_revision(number));
+        match result {
+          Ok(dst) => {
+            emit(&dst)?;
+          }
+          Err(error) => {
+            emit(&format!("error: {:?}\n", error))?;
+          }
+        }
+      }
+      ",symbolic" if !arg.is_empty() => {
+        match pod.symbolic(arg, options.time_quota) {
+          Ok(dst) => {
+            emit(&format!("{}\n", dst))?;
+          }
+          Err(error) => {
+            emit(&format!("error: {:?}\n", error))?;
+          }
+        }
+      }
+      ",optimize" if !arg.is_empty() => {
+        match pod.optimize_term(arg) {
+          Ok(dst) => {
+            emit(&format!("{}\n", dst))?;
+          }
+          Err(error) => {
+            emit(&format!("error: {:?}\n", error))?;
+          }
+        }
+      }
+      ",synthesize" if !arg.is_empty() => {
+        let time_quota = options.time_quota.saturating_mul(64);
+        if let Err(error) = synthesize(&mut pod, arg, time_quota) {
+          emit(&format!("error: {}\n", error))?;
+        }
+      }
+      ",prove" if !arg.is_empty() => {
+        if let Err(error) = prove(&mut pod, arg, options.time_quota) {
+          emit(&format!("error: {}\n", error))?;
+        }
+      }
+      ",save" if !arg.is_empty() => {
+        let result = pod.to_string()
+          .map_err(|error| format!("{:?}", error))
+          .and_then(|dst| {
+            std::fs::write(arg, dst).map_err(|error| error.to_string())
+          });
+        if let Err(error) = result {
+          emit(&format!("error: {}\n", error))?;
+        }
+      }
+      ",load" if !arg.is_empty() => {
+        let result = std::fs::read_to_string(arg)
+          .map_err(|error| error.to_string())
+          .and_then(|src| {
+            pod.load(&src, options.time_quota)
+              .map_err(|error| format!("{:?}", error))
+          });
+        match result {
+          Ok(dst) => {
+            for line in dst.iter() {
+              emit(&format!("{}\n", line))?;
+            }
+          }
+          Err(error) => {
+            emit(&format!("error: {}\n", error))?;
+          }
+        }
+      }
+      _ if command.starts_with(',') => {
+        emit("error: unknown command, see ,help\n")?;
+      }
+      _ => {
+        match pod.eval(src, options.time_quota) {
+          Ok(dst) => {
+            emit(&format!("{}\n", dst))?;
+            emit(&format!("[steps] {}\n", pod.steps()))?;
+          }
+          Err(error) => {
+            emit(&format!("error: {:?}\n", error))?;
+          }
+        }
+      }
+    }
+  }
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let options = match parse_options(&args) {
+    Some(options) => options,
+    None => {
+      eprintln!("{}", USAGE);
+      std::process::exit(2);
+    }
+  };
+  let result = match options.command.as_deref() {
+    None => repl(&options),
+    Some("run") => run(&options),
+    Some("test") => test(&options),
+    Some("fmt") => fmt(&options),
+    Some("graph") => graph(&options),
+    Some("types") => types(&options),
+    Some("suggest") => suggest(&options),
+    Some("lsp") => lsp(&options),
+    Some(_) => {
+      eprintln!("{}", USAGE);
+      std::process::exit(2);
+    }
+  };
+  if let Err(error) = result {
+    eprintln!("error: {}", error);
+    std::process::exit(1);
+  }
+}
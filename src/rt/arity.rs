@@ -0,0 +1,315 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+
+/// A place where a term would run out of values: running the item at
+/// `position` in the term, perhaps deep inside a word it names, needs
+/// `needed` values, but only `available` would be on the stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Underflow {
+  pub position: usize,
+  pub item: String,
+  pub needed: usize,
+  pub available: usize,
+}
+
+/// What the pass knows about a value on the stack.
+#[derive(Clone)]
+enum Abstract {
+  Unknown,
+  /// A block whose code is known.
+  Code(Rc<Vec<Step>>),
+}
+
+#[derive(Clone)]
+enum Step {
+  Run(Gc),
+  Push(Abstract),
+}
+
+/// How far the pass got through some code.
+#[derive(PartialEq)]
+enum Flow {
+  Done,
+  /// The code underflowed, and the rest of it is stuck.
+  Stuck,
+  /// The stack can no longer be predicted.
+  Lost,
+}
+
+/// How many items the pass may run, counting those inside applied
+/// blocks and words, before it gives up on a term.
+const FUEL: usize = 4096;
+
+/// How deeply the pass may nest applied blocks and words.
+const MAX_DEPTH: usize = 256;
+
+/// The most values a term may be checked against, or a definition
+/// declare it takes.
+const MAX_INPUTS: usize = 4096;
+
+/// Simulates a term's stack the way `Thread::step` would, without
+/// reducing it. The pass stops predicting once the stack depends on
+/// something it cannot see, like an unknown block being applied.
+struct Arity<'a> {
+  heap: &'a Heap,
+  tab: &'a Library,
+  host: &'a Host,
+  stack: Vec<Abstract>,
+  position: usize,
+  current: Gc,
+  fuel: usize,
+  depth: usize,
+  dst: Vec<Underflow>,
+}
+
+impl<'a> Arity<'a> {
+  /// Checks that the stack holds enough values. If not, records the
+  /// underflow against the current item and, as `Thread::thunk` does,
+  /// sets the stack aside.
+  fn need(&mut self, needed: usize) -> Result<bool> {
+    if self.stack.len() >= needed {
+      return Ok(true);
+    }
+    let mut item = String::new();
+    quote(self.current, self.heap, &mut item)?;
+    let underflow = Underflow {
+      position: self.position,
+      item: item,
+      needed: needed,
+      available: self.stack.len(),
+    };
+    self.dst.push(underflow);
+    self.stack.clear();
+    return Ok(false);
+  }
+
+  fn run(&mut self, steps: &[Step]) -> Result<Flow> {
+    if self.depth == MAX_DEPTH {
+      return Ok(Flow::Lost);
+    }
+    self.depth += 1;
+    let flow = self.run_steps(steps);
+    self.depth -= 1;
+    return flow;
+  }
+
+  fn run_steps(&mut self, steps: &[Step]) -> Result<Flow> {
+    for step in steps.iter() {
+      let flow = match step {
+        &Step::Run(code) => self.item(code)?,
+        &Step::Push(ref value) => {
+          self.stack.push(value.clone());
+          Flow::Done
+        }
+      };
+      if flow != Flow::Done {
+        return Ok(flow);
+      }
+    }
+    return Ok(Flow::Done);
+  }
+
+  fn item(&mut self, code: Gc) -> Result<Flow> {
+    if self.fuel == 0 {
+      return Ok(Flow::Lost);
+    }
+    self.fuel -= 1;
+    match self.heap.get_ref(code)? {
+      &Object::Block(body) => {
+        let steps = self.heap.get_items(body)?
+          .into_iter().map(Step::Run).collect();
+        self.stack.push(Abstract::Code(Rc::new(steps)));
+      }
+      &Object::Text(_) | &Object::Bytes(_) => {
+        self.stack.push(Abstract::Unknown);
+      }
+      &Object::Opcode(opcode) => {
+        return self.opcode(opcode);
+      }
+      &Object::Word(ref key) => {
+        if let Some(binding) = self.tab.get(key) {
+          let steps: Vec<Step> = self.heap.get_items(*binding)?
+            .into_iter().map(Step::Run).collect();
+          return self.run(&steps);
+        }
+        if let Some(native) = self.host.natives.get(key) {
+          if !self.need(native.arity)? {
+            return Ok(Flow::Stuck);
+          }
+        }
+        return Ok(Flow::Lost);
+      }
+      &Object::Hint(ref name, args) if name.as_ref() == "assert" => {
+        let patterns = self.heap.get_items(args)?;
+        if !self.need(patterns.len())? {
+          return Ok(Flow::Stuck);
+        }
+      }
+      _ => {
+        //
+      }
+    }
+    return Ok(Flow::Done);
+  }
+
+  fn opcode(&mut self, opcode: Opcode) -> Result<Flow> {
+    let needed = match opcode {
+      Opcode::App | Opcode::Box | Opcode::Copy | Opcode::Drop => 1,
+      Opcode::Cat | Opcode::Swap => 2,
+      Opcode::Forall | Opcode::Prop => {
+        return Ok(Flow::Lost);
+      }
+    };
+    if !self.need(needed)? {
+      return Ok(Flow::Stuck);
+    }
+    let top = self.stack.pop().expect("top");
+    match opcode {
+      Opcode::App => {
+        if let Abstract::Code(steps) = top {
+          return self.run(&steps);
+        }
+        return Ok(Flow::Lost);
+      }
+      Opcode::Box => {
+        let steps = vec![Step::Push(top)];
+        self.stack.push(Abstract::Code(Rc::new(steps)));
+      }
+      Opcode::Cat => {
+        let below = self.stack.pop().expect("below");
+        match (below, top) {
+          (Abstract::Code(lhs), Abstract::Code(rhs)) => {
+            let steps = lhs.iter().chain(rhs.iter()).cloned().collect();
+            self.stack.push(Abstract::Code(Rc::new(steps)));
+          }
+          _ => {
+            self.stack.push(Abstract::Unknown);
+          }
+        }
+      }
+      Opcode::Copy => {
+        self.stack.push(top.clone());
+        self.stack.push(top);
+      }
+      Opcode::Drop => {
+        //
+      }
+      Opcode::Swap => {
+        let below = self.stack.pop().expect("below");
+        self.stack.push(top);
+        self.stack.push(below);
+      }
+      Opcode::Forall | Opcode::Prop => {
+        return Err(Error::Bug);
+      }
+    }
+    return Ok(Flow::Done);
+  }
+}
+
+/// Predicts where a term would underflow, given `inputs` values of
+/// which nothing is known. More than `MAX_INPUTS` is a syntax error.
+pub(super) fn check_arity(
+  heap: &Heap,
+  tab: &Library,
+  host: &Host,
+  root: Gc,
+  inputs: usize) -> Result<Vec<Underflow>> {
+  if inputs > MAX_INPUTS {
+    return Err(Error::Syntax);
+  }
+  let mut pass = Arity {
+    heap: heap,
+    tab: tab,
+    host: host,
+    stack: vec![Abstract::Unknown; inputs],
+    position: 0,
+    current: root,
+    fuel: FUEL,
+    depth: 0,
+    dst: Vec::new(),
+  };
+  for (position, code) in heap.get_items(root)?.into_iter().enumerate() {
+    pass.position = position;
+    pass.current = code;
+    if pass.item(code)? == Flow::Lost {
+      break;
+    }
+  }
+  return Ok(pass.dst);
+}
+
+impl Pod {
+  /// Predicts where a term would run out of values if given `inputs`
+  /// values, without reducing it.
+  pub fn arity(&mut self, src: &str, inputs: usize) -> Result<Vec<Underflow>> {
+    let root = parse(src, &mut self.heap)?;
+    let dst = check_arity(&self.heap, &self.tab, &self.host, root, inputs);
+    self.collect()?;
+    return dst;
+  }
+
+  /// Refuses a definition declared to take `inputs` values if it
+  /// would run out of them.
+  pub(super) fn check_declared(&self, root: Gc, inputs: usize) -> Result<()> {
+    let dst = check_arity(&self.heap, &self.tab, &self.host, root, inputs)?;
+    if !dst.is_empty() {
+      return Err(Error::Arity(dst));
+    }
+    return Ok(());
+  }
+}
+
+#[test]
+fn arity() {
+  let src = "```\n:pair b f b f c\n```\n";
+  let mut pod = Pod::from_string(src, 1024, 1024).unwrap();
+  let underflow = |position, item: &str, needed, available| Underflow {
+    position: position,
+    item: item.to_string(),
+    needed: needed,
+    available: available,
+  };
+  assert_eq!(pod.arity("[A] [B] pair", 0), Ok(vec![]));
+  assert_eq!(pod.arity("[A] pair", 0), Ok(vec![underflow(1, "pair", 2, 1)]));
+  assert_eq!(pod.arity("pair", 2), Ok(vec![]));
+  assert_eq!(pod.arity("[f] a e", 1), Ok(vec![
+    underflow(1, "a", 2, 1),
+    underflow(2, "e", 1, 0),
+  ]));
+  assert_eq!(pod.arity("(assert block) d", 0), Ok(vec![
+    underflow(0, "(assert block)", 1, 0),
+    underflow(1, "d", 1, 0),
+  ]));
+  assert_eq!(pod.arity("a f", 0), Ok(vec![
+    underflow(0, "a", 1, 0),
+    underflow(1, "f", 2, 0),
+  ]));
+  assert_eq!(pod.arity("[A] a f", 0), Ok(vec![]));
+  assert!(pod.eval(":two/1 [A] pair", 1024).is_ok());
+  assert_eq!(pod.eval(":two/0 [A] pair", 1024),
+             Err(Error::Arity(vec![underflow(1, "pair", 2, 1)])));
+  assert!(pod.to_string().unwrap().contains(":two/1 [[A]] f b f c"));
+  assert!(pod.eval(":two [A] pair", 1024).is_ok());
+  assert!(pod.to_string().unwrap().contains(":two [[A]] f b f c"));
+  assert_eq!(pod.eval(":k/100000000000 a", 1024), Err(Error::Syntax));
+  assert_eq!(pod.eval(":k/18446744073709551615 a", 1024), Err(Error::Syntax));
+  assert_eq!(pod.eval(":k/18446744073709551616 a", 1024), Err(Error::Syntax));
+  assert_eq!(pod.arity("a", usize::MAX), Err(Error::Syntax));
+}
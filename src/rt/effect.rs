@@ -0,0 +1,737 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use std::fmt;
+
+/// The type of a value on the stack.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+  Var(usize),
+  Text,
+  Bytes,
+  /// A block, with the effect of applying it.
+  Block(Box<Effect>),
+}
+
+/// The type of a stack: values on top of a row variable, which stands
+/// for the rest of the stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stack {
+  pub row: usize,
+  /// The values above the row, bottom first.
+  pub items: Vec<Type>,
+}
+
+/// The stack effect of a term: the stack it expects, and the stack it
+/// leaves in its place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Effect {
+  pub inputs: Stack,
+  pub outputs: Stack,
+}
+
+/// The inferred effect of a library word, with the effect its name
+/// implies, if it follows one of the prelude's naming conventions.
+#[derive(Debug, Clone)]
+pub struct Signature {
+  pub key: Rc<str>,
+  pub effect: Result<Effect>,
+  pub implied: Option<Effect>,
+}
+
+/// Nested blocks deeper than this are not inferred, so that large
+/// numerals cannot exhaust the native stack.
+const MAX_DEPTH: usize = 1024;
+
+fn block(inputs: Stack, outputs: Stack) -> Type {
+  return Type::Block(Box::new(Effect { inputs: inputs, outputs: outputs }));
+}
+
+fn mismatch(message: &str) -> Error {
+  return Error::Type(message.into());
+}
+
+/// Variables of a general effect bound while matching a specific one.
+#[derive(Default)]
+struct Instance {
+  rows: HashMap<usize, usize>,
+  values: HashMap<usize, Type>,
+}
+
+impl Instance {
+  /// Returns true if `specific` is `general` with its variables
+  /// replaced, each the same way wherever it appears.
+  fn effect(&mut self, general: &Effect, specific: &Effect) -> bool {
+    return self.stack(&general.inputs, &specific.inputs) &&
+      self.stack(&general.outputs, &specific.outputs);
+  }
+
+  fn stack(&mut self, general: &Stack, specific: &Stack) -> bool {
+    if general.items.len() != specific.items.len() {
+      return false;
+    }
+    if *self.rows.entry(general.row).or_insert(specific.row) != specific.row {
+      return false;
+    }
+    let items = general.items.iter().zip(specific.items.iter());
+    for (general, specific) in items {
+      if !self.value(general, specific) {
+        return false;
+      }
+    }
+    return true;
+  }
+
+  fn value(&mut self, general: &Type, specific: &Type) -> bool {
+    match (general, specific) {
+      (&Type::Var(var), _) => {
+        let bound = self.values.entry(var).or_insert(specific.clone());
+        return bound == specific;
+      }
+      (&Type::Block(ref general), &Type::Block(ref specific)) => {
+        return self.effect(general, specific);
+      }
+      _ => {
+        return general == specific;
+      }
+    }
+  }
+}
+
+/// Renames the variables of an effect in order of appearance, so that
+/// effects equal up to renaming compare equal.
+fn canonical(effect: &Effect) -> Effect {
+  fn stack(src: &Stack, names: &mut HashMap<usize, usize>) -> Stack {
+    let next = names.len();
+    let row = *names.entry(src.row).or_insert(next);
+    let items = src.items.iter().map(|x| value(x, names)).collect();
+    return Stack { row: row, items: items };
+  }
+  fn value(src: &Type, names: &mut HashMap<usize, usize>) -> Type {
+    match src {
+      &Type::Var(var) => {
+        let next = names.len();
+        return Type::Var(*names.entry(var).or_insert(next));
+      }
+      &Type::Block(ref effect) => {
+        let inputs = stack(&effect.inputs, names);
+        let outputs = stack(&effect.outputs, names);
+        return block(inputs, outputs);
+      }
+      _ => {
+        return src.clone();
+      }
+    }
+  }
+  let mut names = HashMap::new();
+  let inputs = stack(&effect.inputs, &mut names);
+  let outputs = stack(&effect.outputs, &mut names);
+  return Effect { inputs: inputs, outputs: outputs };
+}
+
+/// Writes rows as `..a`, `..b` and values as `A`, `B`, in order of
+/// appearance.
+struct Namer {
+  rows: HashMap<usize, String>,
+  values: HashMap<usize, String>,
+}
+
+impl Namer {
+  fn name(index: usize) -> String {
+    let letter = (b'a' + (index % 26) as u8) as char;
+    if index < 26 {
+      return letter.to_string();
+    }
+    return format!("{}{}", letter, index / 26);
+  }
+
+  fn stack(&mut self, src: &Stack, buf: &mut String) {
+    let next = self.rows.len();
+    let name = self.rows.entry(src.row)
+      .or_insert_with(|| format!("..{}", Namer::name(next)));
+    buf.push_str(name);
+    for item in src.items.iter() {
+      buf.push(' ');
+      self.value(item, buf);
+    }
+  }
+
+  fn value(&mut self, src: &Type, buf: &mut String) {
+    match src {
+      &Type::Var(var) => {
+        let next = self.values.len();
+        let name = self.values.entry(var)
+          .or_insert_with(|| Namer::name(next).to_uppercase());
+        buf.push_str(name);
+      }
+      &Type::Text => {
+        buf.push_str("text");
+      }
+      &Type::Bytes => {
+        buf.push_str("bytes");
+      }
+      &Type::Block(ref effect) => {
+        buf.push('[');
+        self.effect(effect, buf);
+        buf.push(']');
+      }
+    }
+  }
+
+  fn effect(&mut self, src: &Effect, buf: &mut String) {
+    self.stack(&src.inputs, buf);
+    buf.push_str(" -- ");
+    self.stack(&src.outputs, buf);
+  }
+}
+
+impl fmt::Display for Effect {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut namer = Namer {
+      rows: HashMap::new(),
+      values: HashMap::new(),
+    };
+    let mut buf = String::new();
+    namer.effect(self, &mut buf);
+    return f.write_str(&buf);
+  }
+}
+
+impl Signature {
+  /// Returns true if the word has an effect, and it is the one its
+  /// name implies or a more specific one.
+  pub fn is_consistent(&self) -> bool {
+    match (&self.effect, &self.implied) {
+      (&Ok(ref effect), &Some(ref implied)) => {
+        return Instance::default().effect(implied, effect);
+      }
+      (&Ok(_), &None) => {
+        return true;
+      }
+      (&Err(_), _) => {
+        return false;
+      }
+    }
+  }
+
+  /// Describes the signature on one line, for the command line.
+  pub fn describe(&self) -> String {
+    let mut buf = format!("{}: ", self.key);
+    match self.effect {
+      Ok(ref effect) => {
+        buf.push_str(&effect.to_string());
+      }
+      Err(Error::Type(ref message)) => {
+        buf.push_str(&format!("error: {}", message));
+      }
+      Err(ref error) => {
+        buf.push_str(&format!("error: {:?}", error));
+      }
+    }
+    if let (&Ok(_), &Some(ref implied)) = (&self.effect, &self.implied) {
+      if !self.is_consistent() {
+        buf.push_str(&format!(" (its name implies {})", implied));
+      }
+    }
+    return buf;
+  }
+}
+
+/// Stack effect inference by unification, with row variables standing
+/// for the parts of the stack a term leaves alone.
+struct Infer<'a> {
+  heap: &'a Heap,
+  tab: &'a Library,
+  host: &'a Host,
+  next: usize,
+  /// Bindings of value and row variables, which share one numbering.
+  values: HashMap<usize, Type>,
+  rows: HashMap<usize, Stack>,
+  /// Inferred effects of library words, before instantiation.
+  words: HashMap<Rc<str>, Result<Effect>>,
+  active: Vec<Rc<str>>,
+  depth: usize,
+}
+
+impl<'a> Infer<'a> {
+  fn new(heap: &'a Heap, tab: &'a Library, host: &'a Host) -> Self {
+    Infer {
+      heap: heap,
+      tab: tab,
+      host: host,
+      next: 0,
+      values: HashMap::new(),
+      rows: HashMap::new(),
+      words: HashMap::new(),
+      active: Vec::new(),
+      depth: 0,
+    }
+  }
+
+  fn fresh(&mut self) -> usize {
+    self.next += 1;
+    return self.next;
+  }
+
+  fn fresh_value(&mut self) -> Type {
+    return Type::Var(self.fresh());
+  }
+
+  fn fresh_stack(&mut self) -> Stack {
+    return Stack { row: self.fresh(), items: Vec::new() };
+  }
+
+  fn fresh_block(&mut self) -> Type {
+    let inputs = self.fresh_stack();
+    let outputs = self.fresh_stack();
+    return block(inputs, outputs);
+  }
+
+  /// An effect that takes some values from a row and leaves others.
+  fn shuffle(&mut self, inputs: Vec<Type>, outputs: Vec<Type>) -> Effect {
+    let row = self.fresh();
+    return Effect {
+      inputs: Stack { row: row, items: inputs },
+      outputs: Stack { row: row, items: outputs },
+    };
+  }
+
+  fn walk(&self, src: &Type) -> Type {
+    if let &Type::Var(var) = src {
+      if let Some(value) = self.values.get(&var) {
+        return self.walk(value);
+      }
+    }
+    return src.clone();
+  }
+
+  fn walk_stack(&self, src: &Stack) -> Stack {
+    match self.rows.get(&src.row) {
+      Some(base) => {
+        let mut dst = self.walk_stack(base);
+        dst.items.extend(src.items.iter().cloned());
+        return dst;
+      }
+      None => {
+        return src.clone();
+      }
+    }
+  }
+
+  fn occurs(&self, var: usize, src: &Type) -> bool {
+    match self.walk(src) {
+      Type::Var(other) => other == var,
+      Type::Block(effect) => {
+        self.occurs_stack(var, &effect.inputs) ||
+          self.occurs_stack(var, &effect.outputs)
+      }
+      _ => false,
+    }
+  }
+
+  fn occurs_stack(&self, var: usize, src: &Stack) -> bool {
+    let src = self.walk_stack(src);
+    return src.row == var || src.items.iter().any(|x| self.occurs(var, x));
+  }
+
+  /// Unifies a value found on the stack with the one expected.
+  fn unify(&mut self, lhs: &Type, rhs: &Type) -> Result<()> {
+    match (self.walk(lhs), self.walk(rhs)) {
+      (Type::Var(x), Type::Var(y)) if x == y => {
+        return Ok(());
+      }
+      (Type::Var(var), value) | (value, Type::Var(var)) => {
+        if self.occurs(var, &value) {
+          return Err(mismatch("a value would have to contain itself"));
+        }
+        self.values.insert(var, value);
+        return Ok(());
+      }
+      (Type::Text, Type::Text) | (Type::Bytes, Type::Bytes) => {
+        return Ok(());
+      }
+      (Type::Block(lhs), Type::Block(rhs)) => {
+        self.unify_stack(&lhs.inputs, &rhs.inputs)?;
+        return self.unify_stack(&lhs.outputs, &rhs.outputs);
+      }
+      (lhs, rhs) => {
+        let kind = |x: &Type| match x {
+          &Type::Text => "text",
+          &Type::Bytes => "bytes",
+          _ => "a block",
+        };
+        let message = format!("expected {}, found {}", kind(&rhs), kind(&lhs));
+        return Err(Error::Type(message.into()));
+      }
+    }
+  }
+
+  fn unify_stack(&mut self, lhs: &Stack, rhs: &Stack) -> Result<()> {
+    let lhs = self.walk_stack(lhs);
+    let rhs = self.walk_stack(rhs);
+    let common = lhs.items.len().min(rhs.items.len());
+    let lhs_rest = lhs.items.len() - common;
+    let rhs_rest = rhs.items.len() - common;
+    for index in 0..common {
+      self.unify(&lhs.items[lhs_rest + index], &rhs.items[rhs_rest + index])?;
+    }
+    let lhs = self.walk_stack(&Stack {
+      row: lhs.row,
+      items: lhs.items[..lhs_rest].to_vec(),
+    });
+    let rhs = self.walk_stack(&Stack {
+      row: rhs.row,
+      items: rhs.items[..rhs_rest].to_vec(),
+    });
+    if !lhs.items.is_empty() && !rhs.items.is_empty() {
+      return self.unify_stack(&lhs, &rhs);
+    }
+    let (row, stack) = if lhs.items.is_empty() {
+      (lhs.row, rhs)
+    } else {
+      (rhs.row, lhs)
+    };
+    if stack.row == row && stack.items.is_empty() {
+      return Ok(());
+    }
+    if self.occurs_stack(row, &stack) {
+      return Err(mismatch("the stack would have to contain itself"));
+    }
+    self.rows.insert(row, stack);
+    return Ok(());
+  }
+
+  fn resolve(&self, src: &Type) -> Type {
+    match self.walk(src) {
+      Type::Block(effect) => {
+        let effect = self.resolve_effect(&effect);
+        block(effect.inputs, effect.outputs)
+      }
+      value => value,
+    }
+  }
+
+  fn resolve_stack(&self, src: &Stack) -> Stack {
+    let src = self.walk_stack(src);
+    let items = src.items.iter().map(|x| self.resolve(x)).collect();
+    return Stack { row: src.row, items: items };
+  }
+
+  fn resolve_effect(&self, src: &Effect) -> Effect {
+    return Effect {
+      inputs: self.resolve_stack(&src.inputs),
+      outputs: self.resolve_stack(&src.outputs),
+    };
+  }
+
+  /// Copies a resolved effect with fresh variables.
+  fn instantiate(&mut self, src: &Effect) -> Effect {
+    fn value(src: &Type, base: usize, top: &mut usize) -> Type {
+      match src {
+        &Type::Var(var) => {
+          *top = (*top).max(var + 1);
+          return Type::Var(base + var);
+        }
+        &Type::Block(ref effect) => {
+          let inputs = stack(&effect.inputs, base, top);
+          let outputs = stack(&effect.outputs, base, top);
+          return block(inputs, outputs);
+        }
+        _ => {
+          return src.clone();
+        }
+      }
+    }
+    fn stack(src: &Stack, base: usize, top: &mut usize) -> Stack {
+      *top = (*top).max(src.row + 1);
+      let items = src.items.iter().map(|x| value(x, base, top)).collect();
+      return Stack { row: base + src.row, items: items };
+    }
+    let src = canonical(src);
+    let base = self.next + 1;
+    let mut top = 0;
+    let inputs = stack(&src.inputs, base, &mut top);
+    let outputs = stack(&src.outputs, base, &mut top);
+    self.next = base + top;
+    return Effect { inputs: inputs, outputs: outputs };
+  }
+
+  /// Infers the effect of a sequence of items.
+  fn term(&mut self, root: Gc) -> Result<Effect> {
+    let inputs = self.fresh_stack();
+    let mut stack = inputs.clone();
+    for item in self.heap.get_items(root)?.into_iter() {
+      let effect = self.item(item, &stack)?;
+      self.unify_stack(&stack, &effect.inputs)?;
+      stack = effect.outputs;
+    }
+    return Ok(Effect { inputs: inputs, outputs: stack });
+  }
+
+  fn item(&mut self, code: Gc, stack: &Stack) -> Result<Effect> {
+    match self.heap.get_ref(code)? {
+      &Object::Block(body) => {
+        if self.depth >= MAX_DEPTH {
+          return Err(mismatch("blocks are nested too deeply"));
+        }
+        self.depth += 1;
+        let inner = self.term(body);
+        self.depth -= 1;
+        let block = Type::Block(Box::new(inner?));
+        return Ok(self.shuffle(vec![], vec![block]));
+      }
+      &Object::Text(_) => {
+        return Ok(self.shuffle(vec![], vec![Type::Text]));
+      }
+      &Object::Bytes(_) => {
+        return Ok(self.shuffle(vec![], vec![Type::Bytes]));
+      }
+      &Object::Opcode(opcode) => {
+        return self.opcode(opcode, stack);
+      }
+      &Object::Word(ref key) => {
+        return self.word(key);
+      }
+      &Object::Hint(ref name, args) => {
+        return self.hint(name, args);
+      }
+      &Object::Id => {
+        return Ok(self.shuffle(vec![], vec![]));
+      }
+      &Object::Sequence(_, _) => {
+        return Err(Error::Bug);
+      }
+    }
+  }
+
+  fn opcode(&mut self, opcode: Opcode, stack: &Stack) -> Result<Effect> {
+    match opcode {
+      Opcode::App => {
+        let inputs = self.fresh_stack();
+        let outputs = self.fresh_stack();
+        let mut stack = inputs.clone();
+        stack.items.push(block(inputs, outputs.clone()));
+        return Ok(Effect { inputs: stack, outputs: outputs });
+      }
+      Opcode::Box => {
+        let value = self.fresh_value();
+        let row = self.fresh_stack();
+        let mut pushed = row.clone();
+        pushed.items.push(value.clone());
+        return Ok(self.shuffle(vec![value], vec![block(row, pushed)]));
+      }
+      Opcode::Cat => {
+        // Text and bytes concatenate too; pick by what is on the stack.
+        let stack = self.walk_stack(stack);
+        let data = stack.items.iter().rev().take(2)
+          .map(|x| self.walk(x))
+          .find(|x| *x == Type::Text || *x == Type::Bytes);
+        if let Some(data) = data {
+          return Ok(self.shuffle(vec![data.clone(), data.clone()], vec![data]));
+        }
+        let fst = self.fresh_stack();
+        let snd = self.fresh_stack();
+        let thd = self.fresh_stack();
+        let lhs = block(fst.clone(), snd.clone());
+        let rhs = block(snd, thd.clone());
+        let both = block(fst, thd);
+        return Ok(self.shuffle(vec![lhs, rhs], vec![both]));
+      }
+      Opcode::Copy => {
+        let value = self.fresh_value();
+        let copies = vec![value.clone(), value.clone()];
+        return Ok(self.shuffle(vec![value], copies));
+      }
+      Opcode::Drop => {
+        let value = self.fresh_value();
+        return Ok(self.shuffle(vec![value], vec![]));
+      }
+      Opcode::Swap => {
+        let fst = self.fresh_value();
+        let snd = self.fresh_value();
+        return Ok(self.shuffle(vec![fst.clone(), snd.clone()], vec![snd, fst]));
+      }
+      Opcode::Forall | Opcode::Prop => {
+        return Err(mismatch("`g` and `h` have no stack effect"));
+      }
+    }
+  }
+
+  /// Words that cannot be keys, like `A`, are placeholders for any code.
+  fn word(&mut self, key: &Rc<str>) -> Result<Effect> {
+    if !KEY_REGEX.is_match(key) {
+      let inputs = self.fresh_stack();
+      let outputs = self.fresh_stack();
+      return Ok(Effect { inputs: inputs, outputs: outputs });
+    }
+    if !self.words.contains_key(key) {
+      let binding = match self.tab.get(key) {
+        Some(binding) => *binding,
+        None if self.host.natives.contains_key(key) => {
+          let message = format!("native word `{}` has no known effect", key);
+          return Err(Error::Type(message.into()));
+        }
+        None => {
+          let message = format!("undefined word `{}`", key);
+          return Err(Error::Type(message.into()));
+        }
+      };
+      if self.active.contains(key) {
+        let message = format!("`{}` is recursive", key);
+        return Err(Error::Type(message.into()));
+      }
+      self.active.push(key.clone());
+      let effect = self.term(binding).map(|x| self.resolve_effect(&x));
+      self.active.pop();
+      self.words.insert(key.clone(), effect);
+    }
+    let effect = self.words[key].clone()?;
+    return Ok(self.instantiate(&effect));
+  }
+
+  /// `(assert ...)` gives the values it checks their types; other
+  /// hints leave the stack alone.
+  fn hint(&mut self, name: &str, args: Gc) -> Result<Effect> {
+    let mut items = Vec::new();
+    if name == "assert" {
+      for pattern in self.heap.get_items(args)?.into_iter() {
+        let value = match self.heap.get_ref(pattern)? {
+          &Object::Word(ref kind) => {
+            match kind.as_ref() {
+              "any" => self.fresh_value(),
+              "block" | "number" => self.fresh_block(),
+              "text" => Type::Text,
+              "bytes" => Type::Bytes,
+              _ => {
+                return Err(Error::Syntax);
+              }
+            }
+          }
+          &Object::Block(_) => self.fresh_block(),
+          &Object::Text(_) => Type::Text,
+          &Object::Bytes(_) => Type::Bytes,
+          _ => {
+            return Err(Error::Syntax);
+          }
+        };
+        items.push(value);
+      }
+    }
+    return Ok(self.shuffle(items.clone(), items));
+  }
+
+  /// Returns the effect a word's name implies under the prelude's
+  /// conventions: a permutation of the first letters, like `bac`,
+  /// takes and leaves that many values, in any order; `swapN` digs up
+  /// the block beneath the top N; and `opN` runs `op` beneath the top
+  /// N.
+  fn implied(&mut self, key: &str) -> Option<Effect> {
+    let letters: Vec<usize> = key.bytes()
+      .map(|x| x.wrapping_sub(b'a') as usize).collect();
+    let mut sorted = letters.clone();
+    sorted.sort();
+    if letters.len() >= 2 && sorted == (0..letters.len()).collect::<Vec<_>>() {
+      let inputs = letters.iter().map(|_| self.fresh_value()).collect();
+      let outputs = letters.iter().map(|_| self.fresh_value()).collect();
+      return Some(self.shuffle(inputs, outputs));
+    }
+    let base = key.trim_end_matches(|x: char| x.is_ascii_digit());
+    let count: usize = key[base.len()..].parse().ok()?;
+    if base.is_empty() || count == 0 {
+      return None;
+    }
+    let above: Vec<Type> = (0..count).map(|_| self.fresh_value()).collect();
+    if base == "swap" {
+      let below = self.fresh_value();
+      let mut inputs = vec![below.clone()];
+      inputs.extend(above.iter().cloned());
+      let mut outputs = above;
+      outputs.push(below);
+      return Some(self.shuffle(inputs, outputs));
+    }
+    let mut effect = self.word(&base.into()).ok()?;
+    effect.inputs.items.extend(above.iter().cloned());
+    effect.outputs.items.extend(above);
+    return Some(effect);
+  }
+}
+
+impl Pod {
+  /// Infers the stack effect of a term.
+  pub fn infer(&mut self, src: &str) -> Result<Effect> {
+    let root = parse(src, &mut self.heap)?;
+    let mut infer = Infer::new(&self.heap, &self.tab, &self.host);
+    let effect = infer.term(root)?;
+    let effect = infer.resolve_effect(&effect);
+    self.collect()?;
+    return Ok(canonical(&effect));
+  }
+
+  /// Infers the effect of every library word, in order, along with the
+  /// effect its name implies.
+  pub fn signatures(&self) -> Vec<Signature> {
+    let mut infer = Infer::new(&self.heap, &self.tab, &self.host);
+    let mut dst = Vec::new();
+    for key in self.keys().into_iter() {
+      let effect = infer.word(&key).map(|x| canonical(&x));
+      let implied = infer.implied(&key).map(|x| {
+        let x = infer.resolve_effect(&x);
+        return canonical(&x);
+      });
+      let signature = Signature {
+        key: key,
+        effect: effect,
+        implied: implied,
+      };
+      dst.push(signature);
+    }
+    return dst;
+  }
+}
+
+#[test]
+fn effects() {
+  let mut pod = Pod::from_string("", 1024, 1024).unwrap();
+  let mut infer = |src: &str| -> String {
+    match pod.infer(src) {
+      Ok(effect) => effect.to_string(),
+      Err(error) => format!("{:?}", error),
+    }
+  };
+  assert_eq!(infer("f"), "..a A B -- ..a B A");
+  assert_eq!(infer("a"), "..a [..a -- ..b] -- ..b");
+  assert_eq!(infer("b f b f c"), "..a A B -- ..a [..b -- ..b A B]");
+  assert_eq!(infer("\"x\" c"), "..a text -- ..a text");
+  assert_eq!(infer("(assert block) e"), "..a [..b -- ..c] -- ..a");
+  assert_eq!(infer("[B] c"), "..a [..b -- ..c] -- ..a [..b -- ..d]");
+  assert_eq!(infer("d a"), "Type(\"the stack would have to contain itself\")");
+  assert_eq!(infer("\"x\" [] c"), "Type(\"expected text, found a block\")");
+  assert_eq!(infer("frob"), "Type(\"undefined word `frob`\")");
+  let src = concat!(
+    "```\n:swap2 b f b f c f b c a\n",
+    ":bac swap2\n:cab e\n:swap3 f\n```\n");
+  let pod = Pod::from_string(src, 1024, 1024).unwrap();
+  let lines: Vec<String> = pod.signatures().iter()
+    .map(|x| format!("{} {}", x.is_consistent(), x.describe())).collect();
+  assert_eq!(lines, vec![
+    "true bac: ..a A B C -- ..a B C A",
+    "false cab: ..a A -- ..a (its name implies ..a A B C -- ..a D E F)",
+    "true swap2: ..a A B C -- ..a B C A",
+    "false swap3: ..a A B -- ..a B A (its name implies ..a A B C D -- ..a B C D A)",
+  ]);
+}
@@ -0,0 +1,179 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use super::symbolic::{bind, with_variables};
+
+/// How many variables two terms are given when their normal forms
+/// differ.
+const SYMBOLIC_DEPTH: usize = 8;
+
+/// Reduces a term under a quota shared with other reductions, failing
+/// rather than returning a partial result if the quota runs out.
+pub(super) fn reduce_fully(
+  pod: &mut Pod, root: Gc, quota: &mut u64) -> Result<Gc> {
+  let (target, is_done) = reduce_until(
+    root, &mut pod.heap, &pod.tab, &pod.rewrites, &pod.host, quota)?;
+  if !is_done {
+    return Err(Error::Time);
+  }
+  return Ok(target);
+}
+
+impl Pod {
+  /// Returns true if two terms are written the same, item for item,
+  /// ignoring layout and comments. Sundial has no bound variables, so
+  /// this is also alpha-equivalence.
+  pub fn equal(&mut self, lhs: &str, rhs: &str) -> Result<bool> {
+    let lhs = parse(lhs, &mut self.heap)?;
+    let rhs = parse(rhs, &mut self.heap)?;
+    let dst = self.heap.equals(lhs, rhs);
+    self.collect()?;
+    return dst;
+  }
+
+  /// Returns true if two terms behave the same, as far as a bounded
+  /// search can tell. They are equivalent if they reduce to the same
+  /// normal form, or to the same result given eight inputs, as
+  /// `symbolic_over` would give them, deep enough for most terms to
+  /// run to the end. Blocks in the results are compared the same way,
+  /// by their bodies. Variables written as `$N` stand for the same
+  /// value on both sides. Every reduction shares `time_quota`, and
+  /// the check fails with `Error::Time` if it runs out.
+  pub fn equivalent(
+    &mut self, lhs: &str, rhs: &str, time_quota: u64) -> Result<bool> {
+    let lhs = parse(lhs, &mut self.heap)?;
+    let rhs = parse(rhs, &mut self.heap)?;
+    let mut quota = time_quota;
+    let mut base = 0;
+    let dst = bind(&mut self.heap, lhs, &mut base).and_then(|lhs| {
+      let rhs = bind(&mut self.heap, rhs, &mut base)?;
+      return self.compare(lhs, rhs, base, &mut quota);
+    });
+    self.collect()?;
+    return dst;
+  }
+
+  /// Reduces a term as `equivalent` does, spending a quota shared with
+  /// other reductions, and fails with `Error::Time` rather than giving
+  /// a partial result. The library is never changed, so statements
+  /// are refused with `Error::Syntax`.
+  pub fn reduce_term(
+    &mut self, src: &str, quota: &mut u64) -> Result<String> {
+    if is_statement(src) {
+      return Err(Error::Syntax);
+    }
+    let root = parse(src, &mut self.heap)?;
+    let dst = reduce_fully(self, root, quota).and_then(|target| {
+      let mut buf = String::new();
+      quote(target, &self.heap, &mut buf)?;
+      return Ok(buf);
+    });
+    self.collect()?;
+    return dst;
+  }
+
+  /// Compares two terms whose variables are all numbered at most
+  /// `base`, so the inputs they are given are fresh.
+  fn compare(
+    &mut self,
+    lhs: Gc,
+    rhs: Gc,
+    base: usize,
+    quota: &mut u64) -> Result<bool> {
+    let lhs_normal = reduce_fully(self, lhs, quota)?;
+    let rhs_normal = reduce_fully(self, rhs, quota)?;
+    if self.agrees(lhs_normal, rhs_normal, base, quota)? {
+      return Ok(true);
+    }
+    let numbers: Vec<usize> = (base + 1..base + SYMBOLIC_DEPTH + 1).collect();
+    let lhs_input = with_variables(&mut self.heap, lhs, &numbers)?;
+    let rhs_input = with_variables(&mut self.heap, rhs, &numbers)?;
+    let lhs_output = reduce_fully(self, lhs_input, quota)?;
+    let rhs_output = reduce_fully(self, rhs_input, quota)?;
+    let base = base + SYMBOLIC_DEPTH;
+    return self.agrees(lhs_output, rhs_output, base, quota);
+  }
+
+  /// Returns true if two results agree item for item, where blocks
+  /// agree if their bodies are equivalent.
+  fn agrees(
+    &mut self,
+    lhs: Gc,
+    rhs: Gc,
+    base: usize,
+    quota: &mut u64) -> Result<bool> {
+    if self.heap.equals(lhs, rhs)? {
+      return Ok(true);
+    }
+    let lhs_items = self.heap.get_items(lhs)?;
+    let rhs_items = self.heap.get_items(rhs)?;
+    if lhs_items.len() != rhs_items.len() {
+      return Ok(false);
+    }
+    for (lhs, rhs) in lhs_items.into_iter().zip(rhs_items) {
+      if self.heap.equals(lhs, rhs)? {
+        continue;
+      }
+      let bodies = match (self.heap.get_ref(lhs)?, self.heap.get_ref(rhs)?) {
+        (&Object::Block(lhs), &Object::Block(rhs)) => (lhs, rhs),
+        _ => {
+          return Ok(false);
+        }
+      };
+      if !self.compare(bodies.0, bodies.1, base, quota)? {
+        return Ok(false);
+      }
+    }
+    return Ok(true);
+  }
+}
+
+#[test]
+fn equal() {
+  let src = "```\n:pair b f b f c\n```\n";
+  let mut pod = Pod::from_string(src, 4096, 4096).unwrap();
+  assert_eq!(pod.equal("[A]  f # swap\n b", "[A] f b"), Ok(true));
+  let hint = "(assert block) \"x\"";
+  assert_eq!(pod.equal(hint, hint), Ok(true));
+  assert_eq!(pod.equal("[A] f", "[B] f"), Ok(false));
+  assert_eq!(pod.equal("pair", "b f b f c"), Ok(false));
+  assert_eq!(pod.equivalent("[A] [B] pair", "[[A] [B]]", 1024), Ok(true));
+  assert_eq!(pod.equivalent("pair", "b f b f c", 1024), Ok(true));
+  assert_eq!(pod.equivalent("f f", "", 1024), Ok(true));
+  assert_eq!(pod.equivalent("d e", "", 1024), Ok(true));
+  assert_eq!(pod.equivalent("[f] a", "f", 1024), Ok(true));
+  assert_eq!(pod.equivalent("f", "", 1024), Ok(false));
+  assert_eq!(pod.equivalent("e", "[] a", 1024), Ok(false));
+  assert_eq!(pod.equivalent("$1 $2 f", "$2 $1", 1024), Ok(true));
+  assert_eq!(pod.equivalent("$1 $2", "$2 $1", 1024), Ok(false));
+  assert_eq!(pod.equivalent("[pair]", "[b f b f c]", 1024), Ok(true));
+  assert_eq!(pod.equivalent("[A] [f f]", "[A] []", 1024), Ok(true));
+  assert_eq!(pod.equivalent("[[A] f]", "[f]", 1024), Ok(false));
+  assert_eq!(pod.equivalent("[d a] d a", "", 1024), Err(Error::Time));
+  let mut steps = 0;
+  for src in ["[A] [B] pair", "[[A] [B]]"].iter() {
+    steps += pod.eval(src, 1024).map(|_| pod.steps()).unwrap();
+  }
+  assert_eq!(pod.equivalent("[A] [B] pair", "[[A] [B]]", steps), Ok(true));
+  let mut quota = 1024;
+  let pair = pod.reduce_term("[A] [B] pair", &mut quota);
+  assert_eq!(pair, Ok("[[A] [B]]".to_string()));
+  assert_eq!(pod.reduce_term(":pair f", &mut quota), Err(Error::Syntax));
+  assert_eq!(pod.reduce_term("[d a] d a", &mut quota), Err(Error::Time));
+  assert_eq!(quota, 0);
+}
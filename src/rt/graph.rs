@@ -0,0 +1,234 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+/// The reference graph of a pod's library. A definition refers to the
+/// words its source mentioned, as recorded when it was made, even
+/// though reduction has since inlined most of them. Words that cannot
+/// be keys, like `A`, are placeholders and are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+  /// The words each key's definition refers to.
+  pub edges: BTreeMap<Rc<str>, BTreeSet<Rc<str>>>,
+  /// Words referred to but bound neither in the library nor natively.
+  pub undefined: BTreeSet<Rc<str>>,
+  /// Keys no other definition refers to.
+  pub unused: BTreeSet<Rc<str>>,
+  /// Groups of keys that refer to each other, directly or through
+  /// other keys, including keys that refer to themselves.
+  pub cycles: Vec<Vec<Rc<str>>>,
+}
+
+/// The words a definition refers to.
+pub(super) type References = BTreeSet<Rc<str>>;
+
+/// Collects the words a term mentions outside of hint arguments.
+pub(super) fn references(
+  heap: &Heap, root: Gc, dst: &mut References) -> Result<()> {
+  match heap.get_ref(root)? {
+    &Object::Word(ref key) if KEY_REGEX.is_match(key) => {
+      dst.insert(key.clone());
+    }
+    &Object::Block(body) => {
+      references(heap, body, dst)?;
+    }
+    &Object::Sequence(fst, snd) => {
+      references(heap, fst, dst)?;
+      references(heap, snd, dst)?;
+    }
+    _ => {
+      //
+    }
+  }
+  return Ok(());
+}
+
+/// The state of Tarjan's algorithm for strongly connected components.
+struct Components<'a> {
+  edges: &'a BTreeMap<Rc<str>, BTreeSet<Rc<str>>>,
+  index: HashMap<Rc<str>, usize>,
+  low: HashMap<Rc<str>, usize>,
+  stack: Vec<Rc<str>>,
+  on_stack: HashSet<Rc<str>>,
+  dst: Vec<Vec<Rc<str>>>,
+}
+
+impl<'a> Components<'a> {
+  fn visit(&mut self, key: &Rc<str>) {
+    let index = self.index.len();
+    self.index.insert(key.clone(), index);
+    self.low.insert(key.clone(), index);
+    self.stack.push(key.clone());
+    self.on_stack.insert(key.clone());
+    for next in self.edges[key].iter() {
+      if !self.edges.contains_key(next) {
+        continue;
+      }
+      if !self.index.contains_key(next) {
+        self.visit(next);
+        let low = self.low[key].min(self.low[next]);
+        self.low.insert(key.clone(), low);
+      } else if self.on_stack.contains(next) {
+        let low = self.low[key].min(self.index[next]);
+        self.low.insert(key.clone(), low);
+      }
+    }
+    if self.low[key] != self.index[key] {
+      return;
+    }
+    let mut component = Vec::new();
+    while let Some(next) = self.stack.pop() {
+      self.on_stack.remove(&next);
+      let is_root = &next == key;
+      component.push(next);
+      if is_root {
+        break;
+      }
+    }
+    let is_cycle = component.len() > 1 || self.edges[key].contains(key);
+    if is_cycle {
+      component.sort();
+      self.dst.push(component);
+    }
+  }
+}
+
+impl Graph {
+  /// Returns the keys whose definitions refer to a word.
+  pub fn users(&self, key: &str) -> Vec<Rc<str>> {
+    return self.edges.iter()
+      .filter(|&(_, refs)| refs.contains(key))
+      .map(|(user, _)| user.clone())
+      .collect();
+  }
+
+  /// Returns the keys whose meaning depends on a word, directly or
+  /// through other keys.
+  pub fn dependents(&self, key: &str) -> Vec<Rc<str>> {
+    let mut seen = BTreeSet::new();
+    let mut todo: Vec<Rc<str>> = vec![key.into()];
+    while let Some(next) = todo.pop() {
+      for user in self.users(&next) {
+        if &*user != key && seen.insert(user.clone()) {
+          todo.push(user);
+        }
+      }
+    }
+    return seen.into_iter().collect();
+  }
+
+  /// Describes the graph one line per fact, for the command line.
+  pub fn report(&self) -> String {
+    let mut buf = String::new();
+    let join = |xs: &mut dyn Iterator<Item=&Rc<str>>| -> String {
+      return xs.map(|x| &**x).collect::<Vec<&str>>().join(" ");
+    };
+    for (key, refs) in self.edges.iter() {
+      let line = format!("{}: {}", key, join(&mut refs.iter()));
+      buf.push_str(line.trim_end());
+      buf.push('\n');
+    }
+    for key in self.undefined.iter() {
+      let users = self.users(key);
+      buf.push_str(&format!(
+        "undefined: {} (used by {})\n", key, join(&mut users.iter())));
+    }
+    if !self.unused.is_empty() {
+      buf.push_str(&format!("unused: {}\n", join(&mut self.unused.iter())));
+    }
+    for cycle in self.cycles.iter() {
+      buf.push_str(&format!("cycle: {}\n", join(&mut cycle.iter())));
+    }
+    return buf;
+  }
+}
+
+impl Pod {
+  /// Computes the reference graph of the library.
+  pub fn graph(&self) -> Result<Graph> {
+    let mut edges = BTreeMap::new();
+    for key in self.tab.keys() {
+      let refs = self.references.get(key).cloned().unwrap_or_default();
+      edges.insert(key.clone(), refs);
+    }
+    let mut undefined = BTreeSet::new();
+    let mut unused: BTreeSet<Rc<str>> = edges.keys().cloned().collect();
+    for (key, refs) in edges.iter() {
+      for next in refs.iter() {
+        if next != key {
+          unused.remove(next);
+        }
+        if !self.is_defined(next) {
+          undefined.insert(next.clone());
+        }
+      }
+    }
+    let mut components = Components {
+      edges: &edges,
+      index: HashMap::new(),
+      low: HashMap::new(),
+      stack: Vec::new(),
+      on_stack: HashSet::new(),
+      dst: Vec::new(),
+    };
+    for key in edges.keys() {
+      if !components.index.contains_key(key) {
+        components.visit(key);
+      }
+    }
+    let mut cycles = components.dst;
+    cycles.sort();
+    return Ok(Graph {
+      edges: edges,
+      undefined: undefined,
+      unused: unused,
+      cycles: cycles,
+    });
+  }
+}
+
+#[test]
+fn graph() {
+  let src = "```
+:swap f
+:pair b swap b swap c
+:both [pair]
+:even [odd]
+:odd [even]
+:loop [loop] a
+:main [[A] [B] both] frob (assert block)
+```
+";
+  let pod = Pod::from_string(src, 1024, 1024).unwrap();
+  let graph = pod.graph().unwrap();
+  let keys = |xs: &[&str]| -> BTreeSet<Rc<str>> {
+    return xs.iter().map(|&x| x.into()).collect();
+  };
+  assert_eq!(graph.edges["swap"], keys(&[]));
+  assert_eq!(graph.edges["pair"], keys(&["swap"]));
+  assert_eq!(graph.edges["main"], keys(&["both", "frob"]));
+  assert_eq!(graph.undefined, keys(&["frob"]));
+  assert_eq!(graph.unused, keys(&["loop", "main"]));
+  let cycles: Vec<Vec<&str>> = graph.cycles.iter()
+    .map(|xs| xs.iter().map(|x| &**x).collect()).collect();
+  assert_eq!(cycles, vec![vec!["even", "odd"], vec!["loop"]]);
+  assert_eq!(graph.users("pair"), vec!["both".into()] as Vec<Rc<str>>);
+  assert_eq!(graph.users("swap"), vec!["pair".into()] as Vec<Rc<str>>);
+}
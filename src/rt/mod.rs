@@ -16,7 +16,7 @@
 // <https://www.gnu.org/licenses/.
 
 /// An error that might occur during computation.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
   Time,
   Space,
@@ -24,17 +24,189 @@ pub enum Error {
   Stub,
   Bug,
   Null,
-  Assert,
+  Assert(Rc<str>),
+  /// A strict pod refused to change a word these keys depend on.
+  Dependents(Vec<Rc<str>>),
+  /// A term has no consistent stack effect.
+  Type(Rc<str>),
+  /// No state of the library is kept for the revision asked for.
+  Revision,
+  /// A definition would run out of the values it declares it takes.
+  Arity(Vec<Underflow>),
   Syntax,
   Underflow,
   Home,
 }
 
+use std::rc::Rc;
+
 /// The result of a computation.
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Returns true if every `[` or `(` in the source is closed.
+pub fn is_balanced(src: &str) -> bool {
+  match tokenize(src) {
+    Ok(tokens) => {
+      let mut depth = 0;
+      for token in tokens.iter() {
+        match token {
+          Token::Open | Token::HintOpen(_) => {
+            depth += 1;
+          }
+          Token::Close | Token::HintClose => {
+            depth -= 1;
+          }
+          _ => {
+            //
+          }
+        }
+      }
+      return depth <= 0;
+    }
+    Err(_) => {
+      return false;
+    }
+  }
+}
+
+/// Splits the body of a fenced block into statements, each with the
+/// index of the line it starts on. A statement continues onto indented
+/// lines, and onto any line while its brackets are unbalanced. A run
+/// of blank lines between statements becomes an empty statement.
+fn split_statements(src: &str) -> Vec<(usize, String)> {
+  let mut dst: Vec<(usize, String)> = Vec::new();
+  let mut pending = String::new();
+  let mut start = 0;
+  for (index, line) in src.lines().enumerate() {
+    let is_blank = line.trim().is_empty();
+    let is_continued = !is_balanced(&pending) || (
+      line.starts_with(char::is_whitespace) && !is_blank);
+    if !pending.is_empty() && is_continued {
+      pending.push('\n');
+      pending.push_str(line);
+      continue;
+    }
+    if !pending.is_empty() {
+      dst.push((start, pending));
+      pending = String::new();
+    }
+    if !is_blank {
+      pending.push_str(line);
+      start = index;
+    } else if dst.last().map(|x| !x.1.is_empty()).unwrap_or(false) {
+      dst.push((index, String::new()));
+    }
+  }
+  if !pending.is_empty() {
+    dst.push((start, pending));
+  }
+  if dst.last().map(|x| x.1.is_empty()).unwrap_or(false) {
+    dst.pop();
+  }
+  return dst;
+}
+
+/// A statement of a pod file's code or test blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+  /// The line the statement starts on, counting from zero.
+  pub line: usize,
+  pub source: String,
+  /// True if the statement declares a test rather than code.
+  pub is_test: bool,
+}
+
+/// Lists the statements of a literate pod file, as `Pod::load` would
+/// read them.
+pub fn statements(src: &str) -> Vec<Statement> {
+  let mut dst = Vec::new();
+  let mut fence: Option<(usize, Option<bool>)> = None;
+  let mut body = String::new();
+  let mut flush = |start: usize, is_test: bool, body: &str| {
+    for (offset, source) in split_statements(body) {
+      if !source.is_empty() {
+        let statement = Statement {
+          line: start + offset,
+          source: source,
+          is_test: is_test,
+        };
+        dst.push(statement);
+      }
+    }
+  };
+  for (index, line) in src.lines().enumerate() {
+    let info = FENCE_REGEX.captures(line)
+      .map(|x| x.get(1).expect("info").as_str());
+    match (fence, info) {
+      (None, Some(info)) => {
+        let kind = if is_code_fence(info) {
+          Some(false)
+        } else if info == "test" {
+          Some(true)
+        } else {
+          None
+        };
+        fence = Some((index + 1, kind));
+      }
+      (None, None) => {
+        //
+      }
+      (Some(_), None) => {
+        body.push_str(line);
+        body.push('\n');
+      }
+      (Some((start, kind)), Some(_)) => {
+        if let Some(is_test) = kind {
+          flush(start, is_test, &body);
+        }
+        body.clear();
+        fence = None;
+      }
+    }
+  }
+  if let Some((start, Some(is_test))) = fence {
+    flush(start, is_test, &body);
+  }
+  return dst;
+}
+
+/// Lists the words a term refers to, with their byte offsets. Atoms
+/// inside a hint are its arguments rather than references, and the
+/// head of a definition names a key rather than using one, as do the
+/// variables and arrow of a rewrite rule.
+pub fn words(src: &str) -> Result<Vec<(usize, &str)>> {
+  let mut dst = Vec::new();
+  let mut depth = 0;
+  for token in tokenize(src)? {
+    match token {
+      Token::HintOpen(_) => {
+        depth += 1;
+      }
+      Token::HintClose => {
+        depth -= 1;
+      }
+      Token::Atom(atom) if depth == 0 => {
+        let is_opcode = atom.len() == 1 &&
+          atom.chars().all(|x| x.is_lowercase());
+        let is_numeral = atom.chars().all(|x| x.is_ascii_digit());
+        let is_head = atom.starts_with(':') || atom.starts_with('~') ||
+          atom.starts_with('+');
+        let is_rule = atom.starts_with('$') || atom == "=>";
+        if !is_opcode && !is_numeral && !is_head && !is_rule {
+          let offset = atom.as_ptr() as usize - src.as_ptr() as usize;
+          dst.push((offset, atom));
+        }
+      }
+      _ => {
+        //
+      }
+    }
+  }
+  return Ok(dst);
+}
+
 /// A Sundial opcode.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 enum Opcode {
   App,
   Box,
@@ -47,13 +219,14 @@ enum Opcode {
 }
 
 /// Halt the computation if the given condition is false.
-fn assert(flag: Result<bool>) -> Result<()> {
+fn assert<F>(flag: Result<bool>, describe: F) -> Result<()>
+  where F: FnOnce() -> String {
   match flag {
     Ok(true) => {
       return Ok(());
     }
     Ok(false) => {
-      return Err(Error::Assert);
+      return Err(Error::Assert(describe().into()));
     }
     Err(error) => {
       return Err(error);
@@ -61,26 +234,59 @@ fn assert(flag: Result<bool>) -> Result<()> {
   }
 }
 
-pub const WORD_PATTERN: &'static str = r"[a-z0-9-]+";
+pub const WORD_PATTERN: &str = r"[a-z0-9-]+";
 
 lazy_static! {
   static ref WORD_REGEX: regex::Regex = {
     regex::Regex::new(WORD_PATTERN).unwrap()
   };
   static ref POD_INSERT_REGEX: regex::Regex = {
-    let src = format!(r"^:({})\s+(.*)", WORD_PATTERN);
+    let src = format!(r"(?s)^:({})(?:/(\d+))?(!?)\s+(.*)", WORD_PATTERN);
     regex::Regex::new(&src).unwrap()
   };
   static ref POD_DELETE_REGEX: regex::Regex = {
-    let src = format!(r"^~({})\s*", WORD_PATTERN);
+    let src = format!(r"^~({})(!?)\s*", WORD_PATTERN);
+    regex::Regex::new(&src).unwrap()
+  };
+  static ref POD_RULE_REGEX: regex::Regex = {
+    let src = format!(r"(?s)^\+({})\s+(.*)", WORD_PATTERN);
+    regex::Regex::new(&src).unwrap()
+  };
+  static ref POD_UNRULE_REGEX: regex::Regex = {
+    let src = format!(r"^~\+({})\s*", WORD_PATTERN);
     regex::Regex::new(&src).unwrap()
   };
-  static ref HINT_REGEX: regex::Regex = {
-    let src = format!(r"^\(({})\)$", WORD_PATTERN);
+  static ref KEY_REGEX: regex::Regex = {
+    let src = format!(r"^({})$", WORD_PATTERN);
     regex::Regex::new(&src).unwrap()
   };
+  static ref FENCE_REGEX: regex::Regex = {
+    regex::Regex::new(r"^\s*```\s*([a-z0-9-]*)\s*$").unwrap()
+  };
 }
 
+mod pretty;
+mod graph;
+mod effect;
+mod arity;
+mod equal;
+mod symbolic;
+mod optimize;
+mod proof;
+mod rewrite;
+mod property;
+mod text;
+pub use self::pretty::format_term;
+pub use self::pretty::format_pod;
+pub use self::graph::Graph;
+use self::graph::{references, References};
+pub use self::effect::{Effect, Signature, Stack, Type};
+pub use self::arity::Underflow;
+pub use self::symbolic::Symbolic;
+pub use self::proof::{Derivation, Rule};
+pub use self::property::{enumerate, Generator, Property};
+use self::rewrite::{Rewrite, Rewrites};
+
 /// A pointer to some object.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 struct Gc {
@@ -88,16 +294,92 @@ struct Gc {
   generation: u64,
 }
 
-use std::rc::Rc;
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 type Library = HashMap<Rc<str>, Gc>;
 
+/// A value passed between Sundial and the host.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  /// A numeral.
+  Number(u64),
+  Text(Rc<str>),
+  Bytes(Rc<[u8]>),
+  /// Any other term, as source.
+  Code(Rc<str>),
+}
+
+type Function = Rc<dyn Fn(&[Value]) -> Result<Vec<Value>>>;
+
+/// A host function bound to a word. It receives `arity` values from
+/// the environment, bottom first, and its results are evaluated in
+/// their place. If it fails with `Error::Tag`, the values are of the
+/// wrong kinds and the word is left unreduced, as an opcode would be.
+struct Native {
+  arity: usize,
+  function: Function,
+}
+
+/// A host function run when a hint is reached. It sees the hint's
+/// arguments and the environment, bottom first, and may halt the
+/// computation. If it returns values, they replace the environment
+/// and are evaluated in its place, as a native's results are.
+type Handler = Rc<dyn Fn(&[Value], &[Value]) -> Result<Option<Vec<Value>>>>;
+
+/// The host side of a pod.
+struct Host {
+  natives: HashMap<Rc<str>, Native>,
+  hints: HashMap<Rc<str>, Handler>,
+}
+
+impl Host {
+  fn new() -> Self {
+    let mut hints: HashMap<Rc<str>, Handler> = HashMap::new();
+    hints.insert("trace".into(), Rc::new(trace));
+    let mut natives = HashMap::new();
+    text::define_natives(&mut natives);
+    Host {
+      natives: natives,
+      hints: hints,
+    }
+  }
+}
+
+/// Prints the arguments and the environment to stderr, keeping stdout
+/// for the program's own output.
+fn trace(args: &[Value], env: &[Value]) -> Result<Option<Vec<Value>>> {
+  let mut buf = String::new();
+  for value in args.iter().chain(env.iter()) {
+    if !buf.is_empty() {
+      buf.push(' ');
+    }
+    match value {
+      &Value::Number(value) => {
+        buf.push_str(&value.to_string());
+      }
+      &Value::Text(ref value) => {
+        quote_text(value, &mut buf);
+      }
+      &Value::Bytes(ref value) => {
+        quote_bytes(value, &mut buf);
+      }
+      &Value::Code(ref value) => {
+        buf.push_str(value);
+      }
+    }
+  }
+  eprintln!("[trace] {}", buf);
+  return Ok(None);
+}
+
 enum Object {
   Id,
   Opcode(Opcode),
   Word(Rc<str>),
-  Hint(Rc<str>),
+  Hint(Rc<str>, Gc),
+  Text(Rc<str>),
+  Bytes(Rc<[u8]>),
   Block(Gc),
   Sequence(Gc, Gc),
 }
@@ -147,7 +429,21 @@ impl Object {
 
   fn is_hint(&self) -> bool {
     match self {
-      Object::Hint(_) => true,
+      Object::Hint(_, _) => true,
+      _ => false,
+    }
+  }
+
+  fn is_text(&self) -> bool {
+    match self {
+      Object::Text(_) => true,
+      _ => false,
+    }
+  }
+
+  fn is_bytes(&self) -> bool {
+    match self {
+      Object::Bytes(_) => true,
       _ => false,
     }
   }
@@ -159,6 +455,11 @@ impl Object {
     }
   }
 
+  /// Returns true for objects that step onto the environment as is.
+  fn is_value(&self) -> bool {
+    return self.is_block() || self.is_text() || self.is_bytes();
+  }
+
   fn is_sequence(&self) -> bool {
     match self {
       Object::Sequence(_, _) => true,
@@ -205,8 +506,18 @@ impl Heap {
     return self.put(object);
   }
 
-  fn new_hint(&mut self, value: Rc<str>) -> Result<Gc> {
-    let object = Object::Hint(value);
+  fn new_hint(&mut self, value: Rc<str>, args: Gc) -> Result<Gc> {
+    let object = Object::Hint(value, args);
+    return self.put(object);
+  }
+
+  fn new_text(&mut self, value: Rc<str>) -> Result<Gc> {
+    let object = Object::Text(value);
+    return self.put(object);
+  }
+
+  fn new_bytes(&mut self, value: Rc<[u8]>) -> Result<Gc> {
+    let object = Object::Bytes(value);
     return self.put(object);
   }
 
@@ -249,11 +560,26 @@ impl Heap {
     return Ok(object.is_hint());
   }
 
+  fn is_text(&self, pointer: Gc) -> Result<bool> {
+    let object = self.get_ref(pointer)?;
+    return Ok(object.is_text());
+  }
+
+  fn is_bytes(&self, pointer: Gc) -> Result<bool> {
+    let object = self.get_ref(pointer)?;
+    return Ok(object.is_bytes());
+  }
+
   fn is_block(&self, pointer: Gc) -> Result<bool> {
     let object = self.get_ref(pointer)?;
     return Ok(object.is_block());
   }
 
+  fn is_value(&self, pointer: Gc) -> Result<bool> {
+    let object = self.get_ref(pointer)?;
+    return Ok(object.is_value());
+  }
+
   fn is_sequence(&self, pointer: Gc) -> Result<bool> {
     let object = self.get_ref(pointer)?;
     return Ok(object.is_sequence());
@@ -283,7 +609,29 @@ impl Heap {
 
   fn get_hint(&self, pointer: Gc) -> Result<Rc<str>> {
     match self.get_ref(pointer)? {
-      &Object::Hint(ref value) => {
+      &Object::Hint(ref value, _) => {
+        return Ok(value.clone());
+      }
+      _ => {
+        return Err(Error::Tag);
+      }
+    }
+  }
+
+  fn get_text(&self, pointer: Gc) -> Result<Rc<str>> {
+    match self.get_ref(pointer)? {
+      &Object::Text(ref value) => {
+        return Ok(value.clone());
+      }
+      _ => {
+        return Err(Error::Tag);
+      }
+    }
+  }
+
+  fn get_bytes(&self, pointer: Gc) -> Result<Rc<[u8]>> {
+    match self.get_ref(pointer)? {
+      &Object::Bytes(ref value) => {
         return Ok(value.clone());
       }
       _ => {
@@ -292,6 +640,17 @@ impl Heap {
     }
   }
 
+  fn get_hint_args(&self, pointer: Gc) -> Result<Gc> {
+    match self.get_ref(pointer)? {
+      &Object::Hint(_, ref args) => {
+        return Ok(*args);
+      }
+      _ => {
+        return Err(Error::Tag);
+      }
+    }
+  }
+
   fn get_block_body(&self, pointer: Gc) -> Result<Gc> {
     match self.get_ref(pointer)? {
       &Object::Block(ref body) => {
@@ -325,6 +684,183 @@ impl Heap {
     }
   }
 
+  /// Concatenates two blocks, texts or byte strings of the same kind.
+  fn cat(&mut self, lhs: Gc, rhs: Gc) -> Result<Option<Gc>> {
+    if self.is_block(lhs)? && self.is_block(rhs)? {
+      let lhs_body = self.get_block_body(lhs)?;
+      let rhs_body = self.get_block_body(rhs)?;
+      let body = self.new_sequence(lhs_body, rhs_body)?;
+      return Ok(Some(self.new_block(body)?));
+    }
+    if self.is_text(lhs)? && self.is_text(rhs)? {
+      let mut value = self.get_text(lhs)?.to_string();
+      value.push_str(&self.get_text(rhs)?);
+      return Ok(Some(self.new_text(value.into())?));
+    }
+    if self.is_bytes(lhs)? && self.is_bytes(rhs)? {
+      let mut value = self.get_bytes(lhs)?.to_vec();
+      value.extend_from_slice(&self.get_bytes(rhs)?);
+      return Ok(Some(self.new_bytes(value.into())?));
+    }
+    return Ok(None);
+  }
+
+  /// Converts an object to a host value.
+  fn get_value(&self, pointer: Gc) -> Result<Value> {
+    if let Some(value) = self.get_numeral(pointer)? {
+      return Ok(Value::Number(value));
+    }
+    match self.get_ref(pointer)? {
+      &Object::Text(ref value) => {
+        return Ok(Value::Text(value.clone()));
+      }
+      &Object::Bytes(ref value) => {
+        return Ok(Value::Bytes(value.clone()));
+      }
+      _ => {
+        let mut buf = String::new();
+        quote(pointer, self, &mut buf)?;
+        return Ok(Value::Code(buf.into()));
+      }
+    }
+  }
+
+  /// Converts a host value to an object.
+  fn new_value(&mut self, value: &Value) -> Result<Gc> {
+    match value {
+      &Value::Number(value) => {
+        return self.new_numeral(value);
+      }
+      &Value::Text(ref value) => {
+        return self.new_text(value.clone());
+      }
+      &Value::Bytes(ref value) => {
+        return self.new_bytes(value.clone());
+      }
+      &Value::Code(ref value) => {
+        return parse(value, self);
+      }
+    }
+  }
+
+  /// Returns true if two terms are syntactically equal.
+  fn equals(&self, lhs: Gc, rhs: Gc) -> Result<bool> {
+    let lhs_items = self.get_items(lhs)?;
+    let rhs_items = self.get_items(rhs)?;
+    if lhs_items.len() != rhs_items.len() {
+      return Ok(false);
+    }
+    for (lhs, rhs) in lhs_items.iter().zip(rhs_items.iter()) {
+      let is_equal = match (self.get_ref(*lhs)?, self.get_ref(*rhs)?) {
+        (&Object::Opcode(lhs), &Object::Opcode(rhs)) => lhs == rhs,
+        (&Object::Word(ref lhs), &Object::Word(ref rhs)) => lhs == rhs,
+        (&Object::Text(ref lhs), &Object::Text(ref rhs)) => lhs == rhs,
+        (&Object::Bytes(ref lhs), &Object::Bytes(ref rhs)) => lhs == rhs,
+        (&Object::Hint(ref lhs, lhs_args), &Object::Hint(ref rhs, rhs_args)) => {
+          lhs == rhs && self.equals(lhs_args, rhs_args)?
+        }
+        (&Object::Block(lhs), &Object::Block(rhs)) => {
+          self.equals(lhs, rhs)?
+        }
+        _ => false,
+      };
+      if !is_equal {
+        return Ok(false);
+      }
+    }
+    return Ok(true);
+  }
+
+  /// Flattens a sequence into its items, skipping identities.
+  fn get_items(&self, pointer: Gc) -> Result<Vec<Gc>> {
+    let mut dst = Vec::new();
+    let mut todo = vec![pointer];
+    while let Some(pointer) = todo.pop() {
+      match self.get_ref(pointer)? {
+        &Object::Id => {
+          //
+        }
+        &Object::Sequence(fst, snd) => {
+          todo.push(snd);
+          todo.push(fst);
+        }
+        _ => {
+          dst.push(pointer);
+        }
+      }
+    }
+    return Ok(dst);
+  }
+
+  /// Builds a sequence from a slice of items.
+  fn new_items(&mut self, items: &[Gc]) -> Result<Gc> {
+    let mut xs = self.new_id()?;
+    for object in items.iter().rev() {
+      xs = self.new_sequence(*object, xs)?;
+    }
+    return Ok(xs);
+  }
+
+  fn has_opcode(&self, pointer: Gc, opcode: Opcode) -> Result<bool> {
+    match self.get_ref(pointer)? {
+      &Object::Opcode(value) => {
+        return Ok(value == opcode);
+      }
+      _ => {
+        return Ok(false);
+      }
+    }
+  }
+
+  /// Creates the numeral for the given natural number. The numeral
+  /// `n` maps `[F]` to `[F ... F]`, with `n` copies of `F`; zero is
+  /// `[e []]` and the successor of `[N]` is `[d [N] a c]`.
+  fn new_numeral(&mut self, value: u64) -> Result<Gc> {
+    let drop = self.new_opcode(Opcode::Drop)?;
+    let nil_body = self.new_id()?;
+    let nil = self.new_block(nil_body)?;
+    let mut body = self.new_items(&[drop, nil])?;
+    for _ in 0..value {
+      let copy = self.new_opcode(Opcode::Copy)?;
+      let app = self.new_opcode(Opcode::App)?;
+      let cat = self.new_opcode(Opcode::Cat)?;
+      let prev = self.new_block(body)?;
+      body = self.new_items(&[copy, prev, app, cat])?;
+    }
+    return self.new_block(body);
+  }
+
+  /// Returns the natural number a block encodes, if it is a numeral.
+  fn get_numeral(&self, pointer: Gc) -> Result<Option<u64>> {
+    let mut value = 0;
+    let mut block = pointer;
+    loop {
+      if !self.is_block(block)? {
+        return Ok(None);
+      }
+      let body = self.get_block_body(block)?;
+      let items = self.get_items(body)?;
+      if items.len() == 2 && self.has_opcode(items[0], Opcode::Drop)? {
+        if !self.is_block(items[1])? {
+          return Ok(None);
+        }
+        let nil_body = self.get_block_body(items[1])?;
+        if !self.get_items(nil_body)?.is_empty() {
+          return Ok(None);
+        }
+        return Ok(Some(value));
+      }
+      if items.len() != 4 ||
+        !self.has_opcode(items[0], Opcode::Copy)? ||
+        !self.has_opcode(items[2], Opcode::App)? ||
+        !self.has_opcode(items[3], Opcode::Cat)? {
+        return Ok(None);
+      }
+      value += 1;
+      block = items[1];
+    }
+  }
+
   fn mark(&mut self, root: Gc) -> Result<()> {
     match &mut self.nodes[root.index] {
       &mut Some(ref mut node) => {
@@ -336,6 +872,9 @@ impl Heap {
           &Object::Block(body) => {
             return self.mark(body);
           }
+          &Object::Hint(_, args) => {
+            return self.mark(args);
+          }
           &Object::Sequence(fst, snd) => {
             self.mark(fst)?;
             return self.mark(snd);
@@ -352,7 +891,6 @@ impl Heap {
   }
 
   fn sweep(&mut self) -> Result<()> {
-    let mut nodes_deleted = 0;
     for maybe_node in self.nodes.iter_mut() {
       let should_delete_node;
       if let Some(ref mut node) = maybe_node {
@@ -367,12 +905,9 @@ impl Heap {
       }
       if should_delete_node {
         *maybe_node = None;
-        nodes_deleted += 1;
       }
     }
     self.generation += 1;
-    println!(
-      "[gc] deleted: {} generation: {}", nodes_deleted, self.generation);
     return Ok(());
   }
 
@@ -404,100 +939,344 @@ impl Heap {
   }
 }
 
-fn parse(src: &str, heap: &mut Heap) -> Result<Gc> {
-  let mut build = Vec::new();
-  let mut stack = Vec::new();
-  let src = src.replace("[", "[ ");
-  let src = src.replace("]", " ]");
-  for word in src.split_whitespace() {
-    match word {
-      "[" => {
-        stack.push(build);
-        build = Vec::new();
+/// A lexical token of Sundial source.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Token<'a> {
+  Open,
+  Close,
+  Atom(&'a str),
+  Text(&'a str),
+  Comment(&'a str),
+  HintOpen(&'a str),
+  HintClose,
+}
+
+/// Splits source into tokens. Comments run from `#` to the end of the
+/// line, or between `{` and a matching `}`; block comments nest. Text
+/// is written `"..."` and byte strings `b"..."`. A hint is written
+/// `(name args...)`.
+fn tokenize(src: &str) -> Result<Vec<Token<'_>>> {
+  return Ok(spans(src)?.into_iter().map(|x| x.1).collect());
+}
+
+/// Splits source into tokens, each with the byte offset it starts at.
+fn spans(src: &str) -> Result<Vec<(usize, Token<'_>)>> {
+  let mut dst = Vec::new();
+  let mut chars = src.char_indices().peekable();
+  while let Some((start, ch)) = chars.next() {
+    match ch {
+      '[' => {
+        dst.push((start, Token::Open));
       }
-      "]" => {
-        let prev = stack.pop().ok_or(Error::Syntax)?;
-        let mut xs = heap.new_id()?;
-        for object in build.iter().rev() {
-          xs = heap.new_sequence(*object, xs)?;
-        }
-        xs = heap.new_block(xs)?;
-        build = prev;
-        build.push(xs);
+      ']' => {
+        dst.push((start, Token::Close));
       }
-      "a" => {
-        let opcode = Opcode::App;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      '(' => {
+        let mut end = src.len();
+        while let Some(&(index, ch)) = chars.peek() {
+          if ch.is_whitespace() || "[]#{}\"()".contains(ch) {
+            end = index;
+            break;
+          }
+          chars.next();
+        }
+        dst.push((start, Token::HintOpen(&src[start + 1..end])));
       }
-      "b" => {
-        let opcode = Opcode::Box;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      ')' => {
+        dst.push((start, Token::HintClose));
       }
-      "c" => {
-        let opcode = Opcode::Cat;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      '#' => {
+        let mut end = src.len();
+        while let Some(&(index, ch)) = chars.peek() {
+          if ch == '\n' {
+            end = index;
+            break;
+          }
+          chars.next();
+        }
+        dst.push((start, Token::Comment(&src[start..end])));
       }
-      "d" => {
-        let opcode = Opcode::Copy;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      '{' => {
+        let mut depth = 1;
+        let mut end = None;
+        for (index, ch) in &mut chars {
+          if ch == '{' {
+            depth += 1;
+          } else if ch == '}' {
+            depth -= 1;
+            if depth == 0 {
+              end = Some(index + 1);
+              break;
+            }
+          }
+        }
+        let end = end.ok_or(Error::Syntax)?;
+        dst.push((start, Token::Comment(&src[start..end])));
       }
-      "e" => {
-        let opcode = Opcode::Drop;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      '}' => {
+        return Err(Error::Syntax);
       }
-      "f" => {
-        let opcode = Opcode::Swap;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      '"' => {
+        let end = skip_text(&mut chars)?;
+        dst.push((start, Token::Text(&src[start..end])));
       }
-      "g" => {
-        let opcode = Opcode::Forall;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      'b' if src[start + 1..].starts_with('"') => {
+        chars.next();
+        let end = skip_text(&mut chars)?;
+        dst.push((start, Token::Text(&src[start..end])));
       }
-      "h" => {
-        let opcode = Opcode::Prop;
-        let object = heap.new_opcode(opcode)?;
-        build.push(object);
+      _ if ch.is_whitespace() => {
+        //
       }
       _ => {
-        if word.len() == 1 {
-          if word.chars().all(|x| x.is_lowercase()) {
-            return Err(Error::Syntax);
+        let mut end = src.len();
+        while let Some(&(index, ch)) = chars.peek() {
+          if ch.is_whitespace() || "[]#{}\"()".contains(ch) {
+            end = index;
+            break;
           }
+          chars.next();
         }
-        if let Some(data) = HINT_REGEX.captures(&word) {
-          let name = data.get(1).ok_or(Error::Bug)?.as_str();
-          let object = heap.new_hint(name.into())?;
-          build.push(object);
-        } else {
-          let object = heap.new_word(word.into())?;
-          build.push(object);
-        }
+        dst.push((start, Token::Atom(&src[start..end])));
       }
     }
   }
-  if !stack.is_empty() {
-    return Err(Error::Syntax);
-  }
-  let mut xs = heap.new_id()?;
-  for object in build.iter().rev() {
-    xs = heap.new_sequence(*object, xs)?;
-  }
-  return Ok(xs);
+  return Ok(dst);
 }
 
-fn quote(root: Gc, heap: &Heap, buf: &mut String) -> Result<()> {
-  match heap.get_ref(root)? {
-    &Object::Id => {
-      //
+/// Advances past the body of a quoted literal, returning its end.
+fn skip_text<I>(chars: &mut I) -> Result<usize>
+  where I: Iterator<Item=(usize, char)> {
+  let mut is_escaped = false;
+  for (index, ch) in chars {
+    if is_escaped {
+      is_escaped = false;
+    } else if ch == '\\' {
+      is_escaped = true;
+    } else if ch == '"' {
+      return Ok(index + 1);
     }
-    &Object::Opcode(ref value) => {
+  }
+  return Err(Error::Syntax);
+}
+
+/// Decodes the escapes of a quoted literal into bytes. Text may use
+/// `\u{...}`, byte strings `\xNN`.
+fn unescape(src: &str, is_bytes: bool) -> Result<Vec<u8>> {
+  let mut dst = Vec::new();
+  let mut chars = src.chars();
+  while let Some(ch) = chars.next() {
+    if ch != '\\' {
+      let mut buf = [0; 4];
+      dst.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+      continue;
+    }
+    match chars.next().ok_or(Error::Syntax)? {
+      'n' => {
+        dst.push(b'\n');
+      }
+      'r' => {
+        dst.push(b'\r');
+      }
+      't' => {
+        dst.push(b'\t');
+      }
+      '0' => {
+        dst.push(0);
+      }
+      '\\' => {
+        dst.push(b'\\');
+      }
+      '"' => {
+        dst.push(b'"');
+      }
+      'x' if is_bytes => {
+        let digits: String = chars.by_ref().take(2).collect();
+        let value = u8::from_str_radix(&digits, 16).or(Err(Error::Syntax))?;
+        dst.push(value);
+      }
+      'u' if !is_bytes => {
+        if chars.next() != Some('{') {
+          return Err(Error::Syntax);
+        }
+        let digits: String = chars.by_ref().take_while(|x| *x != '}').collect();
+        let value = u32::from_str_radix(&digits, 16).or(Err(Error::Syntax))?;
+        let value = std::char::from_u32(value).ok_or(Error::Syntax)?;
+        let mut buf = [0; 4];
+        dst.extend_from_slice(value.encode_utf8(&mut buf).as_bytes());
+      }
+      _ => {
+        return Err(Error::Syntax);
+      }
+    }
+  }
+  return Ok(dst);
+}
+
+/// Writes text as a quoted literal.
+fn quote_text(value: &str, buf: &mut String) {
+  buf.push('"');
+  for ch in value.chars() {
+    match ch {
+      '"' => buf.push_str("\\\""),
+      '\\' => buf.push_str("\\\\"),
+      '\n' => buf.push_str("\\n"),
+      '\r' => buf.push_str("\\r"),
+      '\t' => buf.push_str("\\t"),
+      '\0' => buf.push_str("\\0"),
+      _ if ch.is_control() => {
+        buf.push_str(&format!("\\u{{{:x}}}", ch as u32));
+      }
+      _ => buf.push(ch),
+    }
+  }
+  buf.push('"');
+}
+
+/// Writes a byte string as a quoted literal.
+fn quote_bytes(value: &[u8], buf: &mut String) {
+  buf.push_str("b\"");
+  for byte in value.iter() {
+    match *byte {
+      b'"' => buf.push_str("\\\""),
+      b'\\' => buf.push_str("\\\\"),
+      b'\n' => buf.push_str("\\n"),
+      b'\r' => buf.push_str("\\r"),
+      b'\t' => buf.push_str("\\t"),
+      0x20..=0x7e => buf.push(*byte as char),
+      _ => buf.push_str(&format!("\\x{:02x}", byte)),
+    }
+  }
+  buf.push('"');
+}
+
+fn parse(src: &str, heap: &mut Heap) -> Result<Gc> {
+  let mut build = Vec::new();
+  let mut stack: Vec<(Vec<Gc>, Option<&str>)> = Vec::new();
+  for token in tokenize(src)? {
+    let word = match token {
+      Token::Open => {
+        stack.push((build, None));
+        build = Vec::new();
+        continue;
+      }
+      Token::Close => {
+        let (prev, hint) = stack.pop().ok_or(Error::Syntax)?;
+        if hint.is_some() {
+          return Err(Error::Syntax);
+        }
+        let mut xs = heap.new_items(&build)?;
+        xs = heap.new_block(xs)?;
+        build = prev;
+        build.push(xs);
+        continue;
+      }
+      Token::HintOpen(name) => {
+        if !KEY_REGEX.is_match(name) {
+          return Err(Error::Syntax);
+        }
+        stack.push((build, Some(name)));
+        build = Vec::new();
+        continue;
+      }
+      Token::HintClose => {
+        let (prev, hint) = stack.pop().ok_or(Error::Syntax)?;
+        let name = hint.ok_or(Error::Syntax)?;
+        let args = heap.new_items(&build)?;
+        let xs = heap.new_hint(name.into(), args)?;
+        build = prev;
+        build.push(xs);
+        continue;
+      }
+      Token::Comment(_) => {
+        continue;
+      }
+      Token::Text(literal) => {
+        let object = if literal.starts_with('b') {
+          let value = unescape(&literal[2..literal.len() - 1], true)?;
+          heap.new_bytes(value.into())?
+        } else {
+          let value = unescape(&literal[1..literal.len() - 1], false)?;
+          let value = String::from_utf8(value).or(Err(Error::Syntax))?;
+          heap.new_text(value.into())?
+        };
+        build.push(object);
+        continue;
+      }
+      Token::Atom(word) => word,
+    };
+    match word {
+      "a" => {
+        let opcode = Opcode::App;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      "b" => {
+        let opcode = Opcode::Box;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      "c" => {
+        let opcode = Opcode::Cat;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      "d" => {
+        let opcode = Opcode::Copy;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      "e" => {
+        let opcode = Opcode::Drop;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      "f" => {
+        let opcode = Opcode::Swap;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      "g" => {
+        let opcode = Opcode::Forall;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      "h" => {
+        let opcode = Opcode::Prop;
+        let object = heap.new_opcode(opcode)?;
+        build.push(object);
+      }
+      _ => {
+        if word.len() == 1 && word.chars().all(|x| x.is_lowercase()) {
+          return Err(Error::Syntax);
+        }
+        if word.chars().all(|x| x.is_ascii_digit()) {
+          let value = word.parse().or(Err(Error::Syntax))?;
+          let object = heap.new_numeral(value)?;
+          build.push(object);
+        } else {
+          let object = heap.new_word(word.into())?;
+          build.push(object);
+        }
+      }
+    }
+  }
+  if !stack.is_empty() {
+    return Err(Error::Syntax);
+  }
+  let mut xs = heap.new_id()?;
+  for object in build.iter().rev() {
+    xs = heap.new_sequence(*object, xs)?;
+  }
+  return Ok(xs);
+}
+
+fn quote(root: Gc, heap: &Heap, buf: &mut String) -> Result<()> {
+  match heap.get_ref(root)? {
+    &Object::Id => {
+      //
+    }
+    &Object::Opcode(ref value) => {
       match value {
         Opcode::App => {
           buf.push('a');
@@ -526,14 +1305,28 @@ fn quote(root: Gc, heap: &Heap, buf: &mut String) -> Result<()> {
       }
     }
     &Object::Word(ref value) => {
-      buf.push_str(&value);
+      buf.push_str(value);
     }
-    &Object::Hint(ref value) => {
+    &Object::Hint(ref value, args) => {
       buf.push('(');
-      buf.push_str(&value);
+      buf.push_str(value);
+      if !heap.is_id(args)? {
+        buf.push(' ');
+        quote(args, heap, buf)?;
+      }
       buf.push(')');
     }
+    &Object::Text(ref value) => {
+      quote_text(value, buf);
+    }
+    &Object::Bytes(ref value) => {
+      quote_bytes(value, buf);
+    }
     &Object::Block(body) => {
+      if let Some(value) = heap.get_numeral(root)? {
+        buf.push_str(&value.to_string());
+        return Ok(());
+      }
       buf.push('[');
       quote(body, heap, buf)?;
       buf.push(']');
@@ -549,22 +1342,39 @@ fn quote(root: Gc, heap: &Heap, buf: &mut String) -> Result<()> {
   return Ok(());
 }
 
+/// Reduces a term, spending one unit of the time quota per step.
 fn reduce(
   continuation: Gc,
   heap: &mut Heap,
   tab: &Library,
-  mut time_quota: u64) -> Result<Gc> {
+  rewrites: &Rewrites,
+  host: &Host,
+  time_quota: &mut u64) -> Result<Gc> {
+  let (target, _) = reduce_until(
+    continuation, heap, tab, rewrites, host, time_quota)?;
+  return Ok(target);
+}
+
+/// Reduces a term as `reduce` does, also telling whether it finished
+/// before the time quota ran out.
+fn reduce_until(
+  continuation: Gc,
+  heap: &mut Heap,
+  tab: &Library,
+  rewrites: &Rewrites,
+  host: &Host,
+  time_quota: &mut u64) -> Result<(Gc, bool)> {
   let mut thread = Thread::with_continuation(continuation);
-  while time_quota > 0 && thread.has_continuation() {
-    time_quota -= 1;
-    thread.step(heap, tab)?;
+  while *time_quota > 0 && thread.has_continuation() {
+    *time_quota -= 1;
+    thread.step(heap, tab, rewrites, host)?;
   }
   if thread.has_continuation() {
     let snd = thread.get_continuation(heap)?;
     let fst = thread.get_environment(heap)?;
-    return heap.new_sequence(fst, snd);
+    return Ok((heap.new_sequence(fst, snd)?, false));
   }
-  return thread.get_environment(heap);
+  return Ok((thread.get_environment(heap)?, true));
 }
 
 use std::collections::VecDeque;
@@ -617,6 +1427,7 @@ impl Thread {
     self.frame.con.push_front(data);
   }
 
+  #[allow(dead_code)]
   fn push_continuation_back(&mut self, data: Gc) {
     self.frame.con.push_back(data);
   }
@@ -637,7 +1448,7 @@ impl Thread {
   }
 
   fn is_monadic(&self) -> bool {
-    return self.frame.env.len() >= 1;
+    return !self.frame.env.is_empty();
   }
 
   fn is_dyadic(&self) -> bool {
@@ -667,7 +1478,7 @@ impl Thread {
   }
 
   fn peek_environment(&mut self) -> Result<Gc> {
-    return self.frame.env.last().map(|x| *x).ok_or(Error::Underflow);
+    return self.frame.env.last().copied().ok_or(Error::Underflow);
   }
 
   fn thunk(&mut self, root: Gc) {
@@ -675,17 +1486,87 @@ impl Thread {
     self.frame.err.push(root);
   }
 
+  fn call(&mut self, code: Gc, native: &Native, heap: &mut Heap) -> Result<()> {
+    if self.frame.env.len() < native.arity {
+      self.thunk(code);
+      return Ok(());
+    }
+    let start = self.frame.env.len() - native.arity;
+    let mut args = Vec::with_capacity(native.arity);
+    for pointer in self.frame.env[start..].iter() {
+      args.push(heap.get_value(*pointer)?);
+    }
+    let results = match (native.function)(&args) {
+      Ok(results) => results,
+      Err(Error::Tag) => {
+        self.thunk(code);
+        return Ok(());
+      }
+      Err(error) => {
+        return Err(error);
+      }
+    };
+    self.frame.env.truncate(start);
+    for value in results.iter().rev() {
+      let pointer = heap.new_value(value)?;
+      self.push_continuation_front(pointer);
+    }
+    return Ok(());
+  }
+
+  /// Checks an `(assert ...)` hint. Each argument is a pattern for one
+  /// value at the top of the environment, the last for the top: `any`,
+  /// `block`, `text`, `bytes` or `number` match by kind, and any other
+  /// value must be equal to the one it is matched against. If the
+  /// environment is too short the hint is kept for later.
+  fn check(&mut self, code: Gc, heap: &Heap) -> Result<()> {
+    let patterns = heap.get_items(heap.get_hint_args(code)?)?;
+    if self.frame.env.len() < patterns.len() {
+      self.thunk(code);
+      return Ok(());
+    }
+    let start = self.frame.env.len() - patterns.len();
+    for (pattern, value) in patterns.iter().zip(self.frame.env[start..].iter()) {
+      let flag = match heap.get_ref(*pattern)? {
+        &Object::Word(ref kind) => {
+          match kind.as_ref() {
+            "any" => Ok(true),
+            "block" => heap.is_block(*value),
+            "text" => heap.is_text(*value),
+            "bytes" => heap.is_bytes(*value),
+            "number" => heap.get_numeral(*value).map(|x| x.is_some()),
+            _ => Err(Error::Syntax),
+          }
+        }
+        object if object.is_value() => {
+          heap.equals(*pattern, *value)
+        }
+        _ => Err(Error::Syntax),
+      };
+      assert(flag, || {
+        let mut buf = String::from("expected ");
+        let _ = quote(*pattern, heap, &mut buf);
+        buf.push_str(", found ");
+        let _ = quote(*value, heap, &mut buf);
+        return buf;
+      })?;
+    }
+    return Ok(());
+  }
+
   fn step(
     &mut self,
     heap: &mut Heap,
-    tab: &HashMap<Rc<str>, Gc>) -> Result<()> {
+    tab: &HashMap<Rc<str>, Gc>,
+    rewrites: &Rewrites,
+    host: &Host) -> Result<()> {
     let code = self.pop_continuation(heap)?;
-    if heap.is_block(code)? {
+    if heap.is_value(code)? {
       self.push_environment(code);
     } else if heap.is_opcode(code)? {
       match heap.get_opcode(code)? {
         Opcode::App => {
-          if !self.is_monadic() {
+          if !self.is_monadic() || !heap.is_block(self.peek_environment()?)? {
             self.thunk(code);
             return Ok(());
           }
@@ -709,11 +1590,16 @@ impl Thread {
           }
           let rhs = self.pop_environment()?;
           let lhs = self.pop_environment()?;
-          let rhs_body = heap.get_block_body(rhs)?;
-          let lhs_body = heap.get_block_body(lhs)?;
-          let target_body = heap.new_sequence(lhs_body, rhs_body)?;
-          let target = heap.new_block(target_body)?;
-          self.push_environment(target);
+          match heap.cat(lhs, rhs)? {
+            Some(target) => {
+              self.push_environment(target);
+            }
+            None => {
+              self.push_environment(lhs);
+              self.push_environment(rhs);
+              self.thunk(code);
+            }
+          }
         }
         Opcode::Copy => {
           if !self.is_monadic() {
@@ -747,16 +1633,41 @@ impl Thread {
       }
     } else if heap.is_word(code)? {
       let code_value = heap.get_word(code)?;
-      match tab.get(&code_value) {
-        Some(binding) => {
-          self.push_continuation_front(*binding);
+      if self.rewrite(&code_value, heap, rewrites)? {
+        return Ok(());
+      }
+      if let Some(binding) = tab.get(&code_value) {
+        self.push_continuation_front(*binding);
+      } else if let Some(native) = host.natives.get(&code_value) {
+        self.call(code, native, heap)?;
+      } else {
+        self.thunk(code);
+      }
+      return Ok(());
+    } else if heap.is_hint(code)? {
+      let code_value = heap.get_hint(code)?;
+      if code_value.as_ref() == "assert" {
+        return self.check(code, heap);
+      }
+      if let Some(handler) = host.hints.get(&code_value) {
+        let mut args = Vec::new();
+        for pointer in heap.get_items(heap.get_hint_args(code)?)? {
+          args.push(heap.get_value(pointer)?);
         }
-        None => {
-          self.thunk(code);
+        let mut env = Vec::with_capacity(self.frame.env.len());
+        for pointer in self.frame.env.iter() {
+          env.push(heap.get_value(*pointer)?);
+        }
+        if let Some(results) = handler(&args, &env)? {
+          self.frame.env.clear();
+          for value in results.iter().rev() {
+            let pointer = heap.new_value(value)?;
+            self.push_continuation_front(pointer);
+          }
         }
       }
       return Ok(());
-    } else if heap.is_id(code)? || heap.is_hint(code)? {
+    } else if heap.is_id(code)? {
       return Ok(());
     } else {
       return Err(Error::Bug);
@@ -765,9 +1676,147 @@ impl Thread {
   }
 }
 
+/// A section of a literate pod file.
+#[derive(Clone)]
+enum Chunk {
+  /// Prose, reproduced verbatim.
+  Text(String),
+  /// A fenced code block: its opening fence and the keys it defines,
+  /// with the rules it declares as `+name`.
+  Code(String, Vec<Rc<str>>),
+}
+
+/// The kind of fenced block being read.
+enum Fence {
+  /// Sundial code, with its opening fence.
+  Code(String),
+  /// Test declarations, kept as prose.
+  Test,
+  /// Anything else, kept as prose.
+  Other,
+}
+
+/// A test declared in a pod file: a term and its expected normal form.
+#[derive(Debug, Clone)]
+struct Test {
+  source: String,
+  expected: String,
+}
+
+/// Returns true if a source is a statement that changes the library,
+/// like `:key term`, `~key`, `+name rule` or `~+name`, rather than a
+/// term.
+pub fn is_statement(src: &str) -> bool {
+  let src = src.trim_start();
+  return POD_INSERT_REGEX.is_match(src) || POD_DELETE_REGEX.is_match(src) ||
+    POD_RULE_REGEX.is_match(src) || POD_UNRULE_REGEX.is_match(src);
+}
+
+/// Splits a test declaration `source = expected` at its `=`.
+pub fn split_test(src: &str) -> Option<(&str, &str)> {
+  let mut chars = src.char_indices().peekable();
+  let mut prev = ' ';
+  while let Some((index, ch)) = chars.next() {
+    match ch {
+      '"' => {
+        skip_text(&mut chars).ok()?;
+      }
+      '#' => {
+        for (_, ch) in chars.by_ref() {
+          if ch == '\n' {
+            break;
+          }
+        }
+      }
+      '=' if prev.is_whitespace() => {
+        let rest = &src[index + 1..];
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+          return Some((&src[..index], rest));
+        }
+      }
+      _ => {
+        //
+      }
+    }
+    prev = ch;
+  }
+  return None;
+}
+
+/// The outcome of a test declared in a pod file.
+#[derive(Debug, Clone)]
+pub struct Report {
+  pub source: String,
+  /// The normal form of the expected term.
+  pub expected: String,
+  /// The normal form of the source, or the error reducing it.
+  pub actual: Result<String>,
+}
+
+impl Report {
+  pub fn is_pass(&self) -> bool {
+    return self.actual.as_ref() == Ok(&self.expected);
+  }
+
+  /// Describes a failure, marking where the terms first differ.
+  pub fn diff(&self) -> String {
+    let mut buf = String::new();
+    buf.push_str(&format!("test: {}\n", self.source));
+    buf.push_str(&format!("  expected: {}\n", self.expected));
+    match self.actual {
+      Ok(ref actual) => {
+        buf.push_str(&format!("  actual:   {}\n", actual));
+        let offset = self.expected.chars().zip(actual.chars())
+          .take_while(|&(x, y)| x == y).count();
+        buf.push_str(&" ".repeat(12 + offset));
+        buf.push_str("^\n");
+      }
+      Err(ref error) => {
+        buf.push_str(&format!("  error:    {:?}\n", error));
+      }
+    }
+    return buf;
+  }
+}
+
+/// A state of the library, kept for undo and redo. Terms are never
+/// mutated, so a snapshot only copies the table of roots, along with
+/// the layout of the document that places them.
+struct Revision {
+  number: u64,
+  tab: Library,
+  arities: HashMap<Rc<str>, usize>,
+  references: HashMap<Rc<str>, References>,
+  rewrites: Rewrites,
+  doc: Vec<Chunk>,
+}
+
+/// Returns true if the fence info string marks Sundial code.
+fn is_code_fence(info: &str) -> bool {
+  return info.is_empty() || info == "sundial";
+}
+
 pub struct Pod {
   heap: Heap,
   tab: Library,
+  /// The number of values each key declared it takes, if any.
+  arities: HashMap<Rc<str>, usize>,
+  /// The words each key's definition mentioned before it was reduced.
+  references: HashMap<Rc<str>, References>,
+  rewrites: Rewrites,
+  /// Optimized bodies, kept until the library next changes.
+  optimized: Option<Library>,
+  host: Host,
+  doc: Vec<Chunk>,
+  tests: Vec<Test>,
+  steps: u64,
+  is_strict: bool,
+  /// The current revision, and the last one handed out.
+  revision: u64,
+  revisions: u64,
+  past: VecDeque<Revision>,
+  future: Vec<Revision>,
+  history_limit: usize,
 }
 
 impl Pod {
@@ -775,6 +1824,20 @@ impl Pod {
     Pod {
       heap: heap,
       tab: HashMap::new(),
+      arities: HashMap::new(),
+      references: HashMap::new(),
+      rewrites: Vec::new(),
+      optimized: None,
+      host: Host::new(),
+      doc: Vec::new(),
+      tests: Vec::new(),
+      steps: 0,
+      is_strict: false,
+      revision: 0,
+      revisions: 0,
+      past: VecDeque::new(),
+      future: Vec::new(),
+      history_limit: 64,
     }
   }
 
@@ -784,10 +1847,155 @@ impl Pod {
     time_quota: u64) -> Result<Self> {
     let heap = Heap::with_capacity(space_quota);
     let mut pod = Pod::with_heap(heap);
+    pod.load(src, time_quota)?;
+    pod.clear_history();
+    return Ok(pod);
+  }
+
+  /// Evaluates the fenced code blocks of a literate markdown file,
+  /// remembering the surrounding prose for `to_string`. A statement
+  /// continues onto indented lines, and onto any line while its
+  /// brackets are unbalanced. Blocks marked `test` declare tests,
+  /// which replace those of the file loaded before.
+  pub fn load(&mut self, src: &str, time_quota: u64) -> Result<Vec<String>> {
+    self.tests.clear();
+    let mut dst = Vec::new();
+    let mut text = String::new();
+    let mut fence = None;
+    let mut body = String::new();
     for line in src.lines() {
-      pod.eval(line, time_quota)?;
+      let info = FENCE_REGEX.captures(line)
+        .map(|x| x.get(1).expect("info").as_str());
+      match fence.take() {
+        None => {
+          match info {
+            Some(info) if is_code_fence(info) => {
+              if !text.is_empty() {
+                self.doc.push(Chunk::Text(text));
+                text = String::new();
+              }
+              fence = Some(Fence::Code(line.to_string()));
+              continue;
+            }
+            Some("test") => {
+              fence = Some(Fence::Test);
+            }
+            Some(_) => {
+              fence = Some(Fence::Other);
+            }
+            None => {
+              //
+            }
+          }
+          text.push_str(line);
+          text.push('\n');
+        }
+        Some(Fence::Other) => {
+          if info.is_none() {
+            fence = Some(Fence::Other);
+          }
+          text.push_str(line);
+          text.push('\n');
+        }
+        Some(open) => {
+          if let Fence::Test = open {
+            text.push_str(line);
+            text.push('\n');
+          }
+          if info.is_none() {
+            body.push_str(line);
+            body.push('\n');
+            fence = Some(open);
+            continue;
+          }
+          dst.extend(self.load_block(&body, open, time_quota)?);
+          body.clear();
+        }
+      }
+    }
+    if let Some(open) = fence {
+      dst.extend(self.load_block(&body, open, time_quota)?);
+    }
+    if !text.is_empty() {
+      self.doc.push(Chunk::Text(text));
+    }
+    return Ok(dst);
+  }
+
+  /// Loads the statements of a fenced block. A code block joins the
+  /// document before its statements run, so every revision they make
+  /// saves a layout that matches its library.
+  fn load_block(
+    &mut self,
+    src: &str,
+    fence: Fence,
+    time_quota: u64) -> Result<Vec<String>> {
+    let mut dst = Vec::new();
+    if let Fence::Code(ref open) = fence {
+      self.doc.push(Chunk::Code(open.clone(), Vec::new()));
+    }
+    for (_, statement) in split_statements(src).iter() {
+      if !statement.is_empty() {
+        dst.extend(self.load_statement(statement, &fence, time_quota)?);
+      }
+    }
+    return Ok(dst);
+  }
+
+  /// Evaluates one statement of a code block, recording its key in the
+  /// last block of the document, or records one statement of a test
+  /// block.
+  fn load_statement(
+    &mut self,
+    src: &str,
+    fence: &Fence,
+    time_quota: u64) -> Result<Option<String>> {
+    match fence {
+      &Fence::Code(_) => {
+        //
+      }
+      _ => {
+        let (source, expected) = split_test(src).ok_or(Error::Syntax)?;
+        let test = Test {
+          source: source.trim().to_string(),
+          expected: expected.trim().to_string(),
+        };
+        self.tests.push(test);
+        return Ok(None);
+      }
+    }
+    let dst = self.eval(src, time_quota)?;
+    let (key, is_kept): (Rc<str>, bool) =
+      if let Some(data) = POD_INSERT_REGEX.captures(src) {
+        (data[1].into(), true)
+      } else if let Some(data) = POD_DELETE_REGEX.captures(src) {
+        (data[1].into(), false)
+      } else if let Some(data) = POD_RULE_REGEX.captures(src) {
+        (format!("+{}", &data[1]).into(), true)
+      } else if let Some(data) = POD_UNRULE_REGEX.captures(src) {
+        (format!("+{}", &data[1]).into(), false)
+      } else {
+        return Ok(Some(dst));
+      };
+    self.forget(&key);
+    match self.doc.last_mut() {
+      Some(&mut Chunk::Code(_, ref mut keys)) if is_kept => {
+        keys.push(key);
+      }
+      _ => {
+        //
+      }
+    }
+    return Ok(Some(dst));
+  }
+
+  /// Removes a key from every code block of the document.
+  fn forget(&mut self, key: &Rc<str>) {
+    for chunk in self.doc.iter_mut() {
+      if let &mut Chunk::Code(_, ref mut keys) = chunk {
+        keys.retain(|x| x != key);
+      }
     }
-    return Ok(pod);
   }
 
   pub fn default(space_quota: usize, time_quota: u64) -> Result<Self> {
@@ -797,52 +2005,455 @@ impl Pod {
     return Pod::from_string(&src, space_quota, time_quota);
   }
 
+  /// Evaluates a statement: `:key term` binds a key to the normal form
+  /// of a term, `~key` unbinds it, and anything else is reduced. In
+  /// strict mode, changing or unbinding a word other keys depend on is
+  /// refused unless forced, as in `:key! term` or `~key!`. A key may
+  /// declare how many values it takes, as in `:key/2 term`; the term
+  /// is then checked for underflow before it is reduced. A rewrite
+  /// rule is declared as `+name pattern => replacement` and removed by
+  /// `~+name`; see `Rewrite`.
   pub fn eval(&mut self, src: &str, time_quota: u64) -> Result<String> {
     let mut dst = String::new();
     if let Some(data) = POD_INSERT_REGEX.captures(src) {
       let key: Rc<str> = data.get(1).expect("key").as_str().into();
-      let value_src = data.get(2).expect("value").as_str();
+      if key.chars().all(|x| x.is_ascii_digit()) {
+        // A key of digits alone would read back as a number.
+        return Err(Error::Syntax);
+      }
+      let arity = match data.get(2) {
+        Some(arity) => Some(arity.as_str().parse().or(Err(Error::Syntax))?),
+        None => None,
+      };
+      let is_forced = !data.get(3).expect("force").as_str().is_empty();
+      let value_src = data.get(4).expect("value").as_str();
       let value = parse(value_src, &mut self.heap)?;
+      let mut refs = References::new();
+      references(&self.heap, value, &mut refs)?;
+      if let Some(arity) = arity {
+        if let Err(error) = self.check_declared(value, arity) {
+          self.collect()?;
+          return Err(error);
+        }
+      }
+      let mut quota = time_quota;
       let value = reduce(
-        value, &mut self.heap, &self.tab, time_quota)?;
+        value, &mut self.heap, &self.tab, &self.rewrites, &self.host,
+        &mut quota)?;
+      self.steps = time_quota - quota;
+      if self.is_strict && !is_forced {
+        self.check_dependents(&key, Some(value))?;
+      }
+      self.record();
       self.tab.insert(key.clone(), value);
+      match arity {
+        Some(arity) => self.arities.insert(key.clone(), arity),
+        None => self.arities.remove(&key),
+      };
+      self.references.insert(key.clone(), refs);
       dst.push(':');
       dst.push_str(&key);
+      if let Some(arity) = arity {
+        dst.push_str(&format!("/{}", arity));
+      }
       dst.push(' ');
-      quote(value, &mut self.heap, &mut dst)?;
+      quote(value, &self.heap, &mut dst)?;
     } else if let Some(data) = POD_DELETE_REGEX.captures(src) {
       let key: Rc<str> = data.get(1).expect("key").as_str().into();
+      let is_forced = !data.get(2).expect("force").as_str().is_empty();
+      if self.is_strict && !is_forced {
+        self.check_dependents(&key, None)?;
+      }
+      self.record();
       self.tab.remove(&key);
+      self.arities.remove(&key);
+      self.references.remove(&key);
       dst.push('~');
       dst.push_str(&key);
+    } else if let Some(data) = POD_RULE_REGEX.captures(src) {
+      let name: Rc<str> = data.get(1).expect("name").as_str().into();
+      let root = parse(data.get(2).expect("rule").as_str(), &mut self.heap)?;
+      let rewrite = match Rewrite::new(&mut self.heap, name.clone(), root) {
+        Ok(rewrite) => rewrite,
+        Err(error) => {
+          self.collect()?;
+          return Err(error);
+        }
+      };
+      self.record();
+      rewrite.quote(&self.heap, &mut dst)?;
+      match self.rewrites.iter().position(|x| x.name == name) {
+        Some(index) => {
+          self.rewrites[index] = rewrite;
+        }
+        None => {
+          self.rewrites.push(rewrite);
+        }
+      }
+    } else if let Some(data) = POD_UNRULE_REGEX.captures(src) {
+      let name = data.get(1).expect("name").as_str();
+      self.record();
+      self.rewrites.retain(|x| x.name.as_ref() != name);
+      dst.push_str("~+");
+      dst.push_str(name);
     } else {
-      let source = parse(src, &mut self.heap)?;
-      let target = reduce(
-        source, &mut self.heap, &self.tab, time_quota)?;
-      quote(target, &mut self.heap, &mut dst)?;
+      return self.normalize(src, time_quota);
     }
-    for pointer in self.tab.values() {
-      self.heap.mark(*pointer)?;
+    self.collect()?;
+    return Ok(dst);
+  }
+
+  /// Refuses to rebind a key to a different value, or to unbind it,
+  /// while other keys depend on it.
+  fn check_dependents(&self, key: &str, value: Option<Gc>) -> Result<()> {
+    let current = match self.tab.get(key) {
+      Some(current) => *current,
+      None => {
+        return Ok(());
+      }
+    };
+    if let Some(value) = value {
+      if self.heap.equals(current, value)? {
+        return Ok(());
+      }
     }
-    self.heap.sweep()?;
+    let dependents = self.graph()?.dependents(key);
+    if !dependents.is_empty() {
+      return Err(Error::Dependents(dependents));
+    }
+    return Ok(());
+  }
+
+  /// Makes `eval` refuse to change words other keys depend on.
+  pub fn set_strict(&mut self, is_strict: bool) {
+    self.is_strict = is_strict;
+  }
+
+  /// Reduces a term without touching the library.
+  fn normalize(&mut self, src: &str, time_quota: u64) -> Result<String> {
+    let mut dst = String::new();
+    let source = parse(src, &mut self.heap)?;
+    let mut quota = time_quota;
+    let target = reduce(
+      source, &mut self.heap, &self.tab, &self.rewrites, &self.host,
+      &mut quota)?;
+    self.steps = time_quota - quota;
+    quote(target, &self.heap, &mut dst)?;
+    self.collect()?;
     return Ok(dst);
   }
 
+  /// Frees every object the library cannot reach, in its current state
+  /// or any state kept for undo and redo.
+  fn collect(&mut self) -> Result<()> {
+    let saved = self.past.iter().chain(self.future.iter());
+    let current = Some(&self.tab).into_iter().chain(self.optimized.as_ref());
+    for tab in saved.clone().map(|x| &x.tab).chain(current) {
+      for pointer in tab.values() {
+        self.heap.mark(*pointer)?;
+      }
+    }
+    for rewrites in saved.map(|x| &x.rewrites).chain(Some(&self.rewrites)) {
+      for rewrite in rewrites.iter() {
+        rewrite.mark(&mut self.heap)?;
+      }
+    }
+    return self.heap.sweep();
+  }
+
+  /// Saves the library before a change, forgetting undone changes and
+  /// the oldest states past the history limit.
+  fn record(&mut self) {
+    let revision = Revision {
+      number: self.revision,
+      tab: self.tab.clone(),
+      arities: self.arities.clone(),
+      references: self.references.clone(),
+      rewrites: self.rewrites.clone(),
+      doc: self.doc.clone(),
+    };
+    self.past.push_back(revision);
+    while self.past.len() > self.history_limit {
+      self.past.pop_front();
+    }
+    self.future.clear();
+    self.optimized = None;
+    self.revisions += 1;
+    self.revision = self.revisions;
+  }
+
+  /// Returns the library to its state before the last change. Returns
+  /// false if there is nothing to undo.
+  pub fn undo(&mut self) -> bool {
+    match self.past.pop_back() {
+      Some(revision) => {
+        let current = Revision {
+          number: self.revision,
+          tab: std::mem::replace(&mut self.tab, revision.tab),
+          arities: std::mem::replace(&mut self.arities, revision.arities),
+          references: std::mem::replace(
+            &mut self.references, revision.references),
+          rewrites: std::mem::replace(&mut self.rewrites, revision.rewrites),
+          doc: std::mem::replace(&mut self.doc, revision.doc),
+        };
+        self.future.push(current);
+        self.optimized = None;
+        self.revision = revision.number;
+        return true;
+      }
+      None => {
+        return false;
+      }
+    }
+  }
+
+  /// Reapplies the last undone change. Returns false if there is
+  /// nothing to redo.
+  pub fn redo(&mut self) -> bool {
+    match self.future.pop() {
+      Some(revision) => {
+        let current = Revision {
+          number: self.revision,
+          tab: std::mem::replace(&mut self.tab, revision.tab),
+          arities: std::mem::replace(&mut self.arities, revision.arities),
+          references: std::mem::replace(
+            &mut self.references, revision.references),
+          rewrites: std::mem::replace(&mut self.rewrites, revision.rewrites),
+          doc: std::mem::replace(&mut self.doc, revision.doc),
+        };
+        self.past.push_back(current);
+        self.optimized = None;
+        self.revision = revision.number;
+        return true;
+      }
+      None => {
+        return false;
+      }
+    }
+  }
+
+  /// Forgets every saved state, so the current one is the oldest that
+  /// `undo` returns to. `from_string` does this after loading, so the
+  /// file's own definitions are not undone one by one.
+  pub fn clear_history(&mut self) {
+    self.past.clear();
+    self.future.clear();
+  }
+
+  /// Returns the number of the library's current state. Every change
+  /// through `eval` makes a new revision; numbers are never reused.
+  pub fn revision(&self) -> u64 {
+    return self.revision;
+  }
+
+  /// Lists the revisions that can be shown, oldest first.
+  pub fn revisions(&self) -> Vec<u64> {
+    let past = self.past.iter().map(|x| x.number);
+    let future = self.future.iter().rev().map(|x| x.number);
+    return past.chain(Some(self.revision)).chain(future).collect();
+  }
+
+  /// Quotes every binding of the library as of a revision, in order,
+  /// then its rules.
+  pub fn show_revision(&self, number: u64) -> Result<String> {
+    let (tab, arities, rewrites) = if number == self.revision {
+      (&self.tab, &self.arities, &self.rewrites)
+    } else {
+      let mut saved = self.past.iter().chain(self.future.iter());
+      let revision = saved.find(|x| x.number == number)
+        .ok_or(Error::Revision)?;
+      (&revision.tab, &revision.arities, &revision.rewrites)
+    };
+    let mut keys: Vec<&Rc<str>> = tab.keys().collect();
+    keys.sort();
+    let mut dst = String::new();
+    for key in keys.into_iter() {
+      let arity = arities.get(key).cloned();
+      self.quote_binding(key, tab[key], arity, &mut dst)?;
+    }
+    for rewrite in rewrites.iter() {
+      rewrite.quote(&self.heap, &mut dst)?;
+      dst.push('\n');
+    }
+    return Ok(dst);
+  }
+
+  /// Sets how many past states are kept for undo.
+  pub fn set_history_limit(&mut self, limit: usize) {
+    self.history_limit = limit;
+    while self.past.len() > limit {
+      self.past.pop_front();
+    }
+  }
+
+  /// Runs the tests declared in the pod's files, comparing the normal
+  /// forms of each side under the time quota.
+  pub fn test(&mut self, time_quota: u64) -> Vec<Report> {
+    let mut dst = Vec::new();
+    for test in self.tests.clone().iter() {
+      let report = match self.normalize(&test.expected, time_quota) {
+        Ok(expected) => {
+          Report {
+            source: test.source.clone(),
+            expected: expected,
+            actual: self.normalize(&test.source, time_quota),
+          }
+        }
+        Err(error) => {
+          Report {
+            source: test.source.clone(),
+            expected: test.expected.clone(),
+            actual: Err(error),
+          }
+        }
+      };
+      dst.push(report);
+    }
+    return dst;
+  }
+
+  /// Lists the library's keys in order.
+  pub fn keys(&self) -> Vec<Rc<str>> {
+    let mut keys: Vec<Rc<str>> = self.tab.keys().cloned().collect();
+    keys.sort();
+    return keys;
+  }
+
+  /// Quotes the reduced definition of a key.
+  pub fn lookup(&self, key: &str) -> Option<String> {
+    let value = self.tab.get(key)?;
+    let mut dst = String::new();
+    quote(*value, &self.heap, &mut dst).ok()?;
+    return Some(dst);
+  }
+
+  /// Returns true if a word is bound in the library or by the host.
+  pub fn is_defined(&self, key: &str) -> bool {
+    return self.tab.contains_key(key) || self.host.natives.contains_key(key);
+  }
+
+  /// Returns the number of steps taken by the last reduction.
+  pub fn steps(&self) -> u64 {
+    return self.steps;
+  }
+
+  /// Binds a word to a host function taking `arity` values. Library
+  /// definitions take precedence over native words. A function that
+  /// returns `Error::Tag` leaves the word and its arguments unreduced.
+  pub fn define_native<F>(
+    &mut self, key: &str, arity: usize, function: F) -> Result<()>
+    where F: Fn(&[Value]) -> Result<Vec<Value>> + 'static {
+    if !KEY_REGEX.is_match(key) || key.len() == 1 {
+      return Err(Error::Syntax);
+    }
+    let native = Native {
+      arity: arity,
+      function: Rc::new(function),
+    };
+    self.host.natives.insert(key.into(), native);
+    return Ok(());
+  }
+
+  /// Unbinds a native word.
+  pub fn remove_native(&mut self, key: &str) {
+    self.host.natives.remove(key);
+  }
+
+  /// Runs a host function whenever the hint `(key ...)` is reached.
+  /// The function may return values to replace the environment, so a
+  /// hint like `(memo)` can supply a result it has already computed.
+  /// Hints without a handler do nothing; `(trace)` prints the
+  /// environment and `(assert ...)` is built in.
+  pub fn define_hint<F>(&mut self, key: &str, handler: F) -> Result<()>
+    where F: Fn(&[Value], &[Value]) -> Result<Option<Vec<Value>>> + 'static {
+    if !KEY_REGEX.is_match(key) {
+      return Err(Error::Syntax);
+    }
+    self.host.hints.insert(key.into(), Rc::new(handler));
+    return Ok(());
+  }
+
+  /// Removes the handler for a hint.
+  pub fn remove_hint(&mut self, key: &str) {
+    self.host.hints.remove(key);
+  }
+
+  /// Writes the pod as literate markdown. Definitions are placed in
+  /// the code block that introduced them; new ones go in a final block.
   pub fn to_string(&self) -> Result<String> {
     let mut target = String::new();
+    let mut seen = HashSet::new();
+    for chunk in self.doc.iter() {
+      match chunk {
+        &Chunk::Text(ref text) => {
+          target.push_str(text);
+        }
+        &Chunk::Code(ref open, ref keys) => {
+          target.push_str(open);
+          target.push('\n');
+          for key in keys.iter() {
+            if let Some(value) = self.tab.get(key) {
+              let arity = self.arities.get(key).cloned();
+              self.quote_binding(key, *value, arity, &mut target)?;
+              seen.insert(key.clone());
+            } else if let Some(rewrite) = self.rewrite(key) {
+              rewrite.quote(&self.heap, &mut target)?;
+              target.push('\n');
+              seen.insert(key.clone());
+            }
+          }
+          target.push_str("```\n");
+        }
+      }
+    }
     let mut keys: Vec<Rc<str>> = self.tab.keys()
-      .map(|x| x.clone()).collect();
+      .filter(|x| !seen.contains(*x))
+      .cloned().collect();
     keys.sort();
+    let rewrites: Vec<&Rewrite> = self.rewrites.iter()
+      .filter(|x| !seen.contains(&*format!("+{}", x.name))).collect();
+    if keys.is_empty() && rewrites.is_empty() {
+      return Ok(target);
+    }
+    if !target.is_empty() && !target.ends_with("\n\n") {
+      target.push('\n');
+    }
+    target.push_str("```\n");
     for key in keys.iter() {
       let value = self.tab.get(key).unwrap();
-      target.push(':');
-      target.push_str(&key);
-      target.push(' ');
-      quote(*value, &self.heap, &mut target)?;
+      let arity = self.arities.get(key).cloned();
+      self.quote_binding(key, *value, arity, &mut target)?;
+    }
+    for rewrite in rewrites.into_iter() {
+      rewrite.quote(&self.heap, &mut target)?;
       target.push('\n');
     }
+    target.push_str("```\n");
     return Ok(target);
   }
+
+  /// Finds the rule a document key like `+name` stands for.
+  fn rewrite(&self, key: &str) -> Option<&Rewrite> {
+    let name = key.strip_prefix('+')?;
+    return self.rewrites.iter().find(|x| x.name.as_ref() == name);
+  }
+
+  fn quote_binding(
+    &self,
+    key: &str,
+    value: Gc,
+    arity: Option<usize>,
+    buf: &mut String) -> Result<()> {
+    buf.push(':');
+    buf.push_str(key);
+    if let Some(arity) = arity {
+      buf.push_str(&format!("/{}", arity));
+    }
+    buf.push(' ');
+    quote(value, &self.heap, buf)?;
+    buf.push('\n');
+    return Ok(());
+  }
 }
 
 #[test]
@@ -880,3 +2491,332 @@ fn primitives() {
   check("[A] [B] g", "[A] [B] g");
   check("[A] h", "[A] h");
 }
+
+/// Evaluates each source in turn, checking what it prints.
+#[cfg(test)]
+fn check_evals(pod: &mut Pod, time_quota: u64, cases: &[(&str, &str)]) {
+  for &(source, expected) in cases.iter() {
+    let target = pod.eval(source, time_quota);
+    assert_eq!(target, Ok(expected.to_string()), "{}", source);
+  }
+}
+
+#[test]
+fn comments() {
+  let mut pod = Pod::from_string("", 1024, 1024).unwrap();
+  check_evals(&mut pod, 1024, &[
+    ("[A] # [B]", "[A]"),
+    ("[A] {[B] {c}} [C]", "[A] [C]"),
+    ("[A]#comment\n[B]", "[A] [B]"),
+    ("[A] \"#\" # \"\"", "[A] \"#\""),
+  ]);
+  assert_eq!(pod.eval("[A] {[B]", 1024), Err(Error::Syntax));
+}
+
+#[test]
+fn hint_syntax() {
+  let mut pod = Pod::from_string("", 1024, 1024).unwrap();
+  check_evals(&mut pod, 1024, &[
+    ("(foo) [A] (bar [B] C)", "[A]"),
+    ("[A] (foo (bar))", "[A]"),
+    ("[(foo [B] (bar)) A]", "[(foo [B] (bar)) A]"),
+  ]);
+}
+
+#[test]
+fn literate() {
+  let space = 1024;
+  let time  = 1024;
+  let src = "# Prelude\n\nProse with an unbalanced [ is ignored.\n\n```\n:swap f\n:drop e\n```\n\n```text\n:ignored a\n```\n";
+  let mut pod = Pod::from_string(src, space, time).unwrap();
+  assert_eq!(src, &pod.to_string().unwrap());
+  assert_eq!("[B]", &pod.eval("[A] [B] swap drop", time).unwrap());
+  assert_eq!("[A] ignored", &pod.eval("[A] ignored", time).unwrap());
+  pod.eval(":drop1 swap drop", time).unwrap();
+  pod.eval("~swap", time).unwrap();
+  let expected = "# Prelude\n\nProse with an unbalanced [ is ignored.\n\n```\n:drop e\n```\n\n```text\n:ignored a\n```\n\n```\n:drop1 f e\n```\n";
+  assert_eq!(expected, &pod.to_string().unwrap());
+}
+
+#[test]
+fn prelude() {
+  let space = 4096;
+  let time  = 4096;
+  let src = include_str!("../../lib/default.md");
+  let mut pod = Pod::from_string(src, space, time).unwrap();
+  assert_eq!("[B] [A]", &pod.eval("[A] [B] swap", time).unwrap());
+  assert_eq!("[[A] [B]]", &pod.eval("[A] [B] pair", time).unwrap());
+  assert_eq!("[A]", &pod.eval("[[A] [B]] fst", time).unwrap());
+  for report in pod.test(time).iter() {
+    assert!(report.is_pass(), "{}", report.diff());
+  }
+  for signature in pod.signatures().iter() {
+    assert!(signature.is_consistent(), "{}", signature.describe());
+  }
+}
+
+#[test]
+fn multiline() {
+  let space = 1024;
+  let time  = 1024;
+  let src = "```\n:swap f\n:pair\n  b swap # [[A]] [B]\n  b swap\n  c\n:both [A\n[B]]\n\n[X] [Y] pair\n```\n";
+  let mut pod = Pod::from_string(src, space, time).unwrap();
+  assert_eq!("[A [B]]", &pod.eval("both", time).unwrap());
+  assert_eq!("[[A] [B]]", &pod.eval("[A] [B] pair", time).unwrap());
+  let dst = pod.load("```\n:bad [\n```\n", time);
+  assert!(dst.is_err());
+}
+
+#[test]
+fn numerals() {
+  let space = 4096;
+  let time  = 4096;
+  let src = include_str!("../../lib/default.md");
+  let mut pod = Pod::from_string(src, space, time).unwrap();
+  check_evals(&mut pod, time, &[
+    ("0", "0"),
+    ("12", "12"),
+    ("[e []]", "0"),
+    ("[d [e []] a c]", "1"),
+    ("[A] 3 a", "[A A A]"),
+    ("[A] 0 a", "[]"),
+    ("2 succ", "3"),
+    ("2 3 add", "5"),
+    ("2 3 mul", "6"),
+    ("[A] [b] 3 times", "[[[[A]]]]"),
+  ]);
+  pod.eval("[A] 0 a", time).unwrap();
+  assert_eq!(7, pod.steps());
+  assert_eq!(Err(Error::Syntax), pod.eval(":12 [A]", time));
+  assert_eq!(Ok("12".to_string()), pod.eval("12", time));
+  let dst = Pod::from_string("```\n:12 [A]\n```\n", space, time);
+  assert!(matches!(dst, Err(Error::Syntax)));
+}
+
+#[test]
+fn literals() {
+  let space = 1024;
+  let time  = 1024;
+  let mut pod = Pod::from_string("", space, time).unwrap();
+  check_evals(&mut pod, time, &[
+    ("\"hello, world\"", "\"hello, world\""),
+    ("\"[not] #code\"", "\"[not] #code\""),
+    ("\"a\\\"b\\n\\u{3bb}\"", "\"a\\\"b\\n\u{3bb}\""),
+    ("b\"\\x00ab\\xff\"", "b\"\\x00ab\\xff\""),
+    ("\"ab\" \"cd\" c", "\"abcd\""),
+    ("b\"ab\" b\"cd\" c", "b\"abcd\""),
+    ("\"ab\" b\"cd\" c", "\"ab\" b\"cd\" c"),
+    ("\"ab\" [A] c", "\"ab\" [A] c"),
+    ("\"ab\" a", "\"ab\" a"),
+    ("\"ab\" b", "[\"ab\"]"),
+    ("\"ab\" d", "\"ab\" \"ab\""),
+    ("\"ab\"\"cd\" f", "\"cd\" \"ab\""),
+  ]);
+  assert!(pod.eval("\"open", time).is_err());
+}
+
+#[test]
+fn natives() {
+  let space = 1024;
+  let time  = 1024;
+  let mut pod = Pod::from_string("", space, time).unwrap();
+  pod.define_native("plus", 2, |args| {
+    match (&args[0], &args[1]) {
+      (&Value::Number(lhs), &Value::Number(rhs)) => {
+        return Ok(vec![Value::Number(lhs + rhs)]);
+      }
+      _ => {
+        return Err(Error::Tag);
+      }
+    }
+  }).unwrap();
+  pod.define_native("length", 1, |args| {
+    match &args[0] {
+      &Value::Text(ref value) => {
+        let length = value.chars().count() as u64;
+        return Ok(vec![Value::Number(length)]);
+      }
+      _ => {
+        return Err(Error::Tag);
+      }
+    }
+  }).unwrap();
+  pod.define_native("twice", 1, |args| {
+    return Ok(vec![args[0].clone(), args[0].clone(), Value::Code("c".into())]);
+  }).unwrap();
+  assert!(pod.define_native("a", 0, |_| Ok(vec![])).is_err());
+  check_evals(&mut pod, time, &[
+    ("2 3 plus", "5"),
+    ("3 plus", "3 plus"),
+    ("\"hello\" length", "5"),
+    ("[A] twice", "[A A]"),
+    (":length [A]", ":length [A]"),
+    ("\"hello\" length", "\"hello\" [A]"),
+    ("[A] [B] plus", "[A] [B] plus"),
+    ("[A] [B] plus 2 3 plus", "[A] [B] plus 5"),
+  ]);
+}
+
+#[test]
+fn hints() {
+  use std::cell::RefCell;
+  let space = 1024;
+  let time  = 1024;
+  let mut pod = Pod::from_string("", space, time).unwrap();
+  let seen = Rc::new(RefCell::new(Vec::new()));
+  let log = seen.clone();
+  pod.define_hint("log", move |args, env| {
+    log.borrow_mut().push((args.to_vec(), env.to_vec()));
+    return Ok(None);
+  }).unwrap();
+  pod.define_hint("fail", |_, _| Err(Error::Stub)).unwrap();
+  assert_eq!("[A] [B]", &pod.eval("[A] (log 1) [B] (trace) (par)", time).unwrap());
+  let args = vec![Value::Number(1)];
+  let env = vec![Value::Code("[A]".into())];
+  assert_eq!(vec![(args, env)], *seen.borrow());
+  assert!(pod.eval("[A] (fail)", time).is_err());
+  pod.remove_hint("fail");
+  assert_eq!("[A]", &pod.eval("[A] (fail)", time).unwrap());
+}
+
+#[test]
+fn hint_results() {
+  let space = 1024;
+  let time  = 1024;
+  let mut pod = Pod::from_string("", space, time).unwrap();
+  pod.define_hint("memo", |_, env| {
+    if env == [Value::Code("[A]".into())] {
+      return Ok(Some(vec![Value::Code("[B]".into())]));
+    }
+    return Ok(None);
+  }).unwrap();
+  pod.define_hint("use", |args, _| Ok(Some(args.to_vec()))).unwrap();
+  check_evals(&mut pod, time, &[
+    ("[A] (memo) d", "[B] [B]"),
+    ("[C] (memo) d", "[C] [C]"),
+    ("[A] [B] (use [f] 2) a", "[f f]"),
+    ("[A] (use) [C]", "[C]"),
+  ]);
+}
+
+#[test]
+fn assertions() {
+  let space = 4096;
+  let time  = 4096;
+  let src = include_str!("../../lib/default.md");
+  let mut pod = Pod::from_string(src, space, time).unwrap();
+  check_evals(&mut pod, time, &[
+    ("[A] (assert block)", "[A]"),
+    ("[A] \"B\" (assert any text)", "[A] \"B\""),
+    ("3 (assert number) (assert 3)", "3"),
+    ("[A] [B] (assert [A] [B])", "[A] [B]"),
+    ("(assert block)", "(assert block)"),
+    (":foo (assert block block) c", ":foo (assert block block) c"),
+    ("[A] [B] foo", "[A B]"),
+    ("[[A] [B]] fst", "[A]"),
+  ]);
+  let error = pod.eval("[A] (assert text)", time).unwrap_err();
+  assert_eq!(Error::Assert("expected text, found [A]".into()), error);
+  let error = pod.eval("\"A\" [B] foo", time).unwrap_err();
+  assert_eq!(Error::Assert("expected block, found \"A\"".into()), error);
+  let error = pod.eval("\"A\" fst", time).unwrap_err();
+  assert_eq!(Error::Assert("expected block, found \"A\"".into()), error);
+  let error = pod.eval("[A] (assert [B])", time).unwrap_err();
+  assert_eq!(Error::Assert("expected [B], found [A]".into()), error);
+  assert_eq!(Error::Syntax, pod.eval("[A] (assert a)", time).unwrap_err());
+}
+
+#[test]
+fn tests() {
+  let space = 1024;
+  let time  = 1024;
+  let src = "```\n:swap f\n```\n\n```test\n[A] [B] swap = [B] [A]\n[A] e =\n\"=\" [A]\n  swap = [A] \"=\"\n[A] [B] swap = [A] [B]\n[A] a = a\n```\n";
+  let mut pod = Pod::from_string(src, space, time).unwrap();
+  assert_eq!(src, &pod.to_string().unwrap());
+  let reports = pod.test(time);
+  let passed: Vec<bool> = reports.iter().map(|x| x.is_pass()).collect();
+  assert_eq!(vec![true, true, true, false, false], passed);
+  let diff = "test: [A] [B] swap\n  expected: [A] [B]\n  actual:   [B] [A]\n             ^\n";
+  assert_eq!(diff, &reports[3].diff());
+  pod.load(src, time).unwrap();
+  assert_eq!(pod.test(time).len(), 5);
+  pod.load("```test\n[A] swap = [A] f\n```\n", time).unwrap();
+  assert_eq!(pod.test(time).len(), 1);
+  assert!(Pod::from_string("```test\n[A]\n```\n", space, time).is_err());
+}
+
+#[test]
+fn outline() {
+  let src = concat!(
+    "# Pod\n\n```\n:pair\n  b f b f c\n\n[A] pair\n```\n\n",
+    "```test\n[A] = [A]\n```\n");
+  let statements = statements(src);
+  let lines: Vec<(usize, bool)> = statements.iter()
+    .map(|x| (x.line, x.is_test)).collect();
+  assert_eq!(lines, vec![(3, false), (6, false), (10, true)]);
+  assert_eq!(statements[0].source, ":pair\n  b f b f c");
+  let words = words("[A] dup (assert block) :key 0 b-c").unwrap();
+  assert_eq!(words, vec![(1, "A"), (4, "dup"), (30, "b-c")]);
+}
+
+#[test]
+fn strict() {
+  let src = "```\n:dup d\n:dup2 [dup dup]\n:quad [dup2 dup2]\n```\n";
+  let mut pod = Pod::from_string(src, 1024, 1024).unwrap();
+  pod.set_strict(true);
+  let dependents = vec!["dup2".into(), "quad".into()];
+  assert_eq!(pod.eval(":dup d", 1024), Ok(":dup d".to_string()));
+  let refused = Err(Error::Dependents(dependents));
+  assert_eq!(pod.eval(":dup e", 1024), refused);
+  assert_eq!(pod.eval("~dup", 1024), refused);
+  assert_eq!(pod.eval(":quad [dup]", 1024), Ok(":quad [dup]".to_string()));
+  assert_eq!(pod.eval(":dup! e", 1024), Ok(":dup e".to_string()));
+  assert_eq!(pod.eval("~dup2!", 1024), Ok("~dup2".to_string()));
+  pod.set_strict(false);
+  assert_eq!(pod.eval("~dup", 1024), Ok("~dup".to_string()));
+}
+
+#[test]
+fn history() {
+  let mut pod = Pod::from_string("", 1024, 1024).unwrap();
+  pod.eval(":one 1", 1024).unwrap();
+  pod.eval(":two 2", 1024).unwrap();
+  pod.eval(":one 3", 1024).unwrap();
+  assert_eq!(pod.revision(), 3);
+  assert!(pod.undo());
+  assert!(pod.undo());
+  assert_eq!(pod.revision(), 1);
+  assert_eq!(pod.eval("one", 1024), Ok("1".to_string()));
+  assert_eq!(pod.eval("two", 1024), Ok("two".to_string()));
+  assert!(pod.redo());
+  assert_eq!(pod.revisions(), vec![0, 1, 2, 3]);
+  assert_eq!(pod.show_revision(3), Ok(":one 3\n:two 2\n".to_string()));
+  assert_eq!(pod.show_revision(0), Ok("".to_string()));
+  pod.eval("~two", 1024).unwrap();
+  assert_eq!(pod.revision(), 4);
+  assert!(!pod.redo());
+  pod.set_history_limit(1);
+  assert_eq!(pod.revisions(), vec![2, 4]);
+  assert_eq!(pod.show_revision(1), Err(Error::Revision));
+  assert!(pod.undo());
+  assert!(!pod.undo());
+  assert_eq!(pod.eval("two", 1024), Ok("2".to_string()));
+}
+
+#[test]
+fn history_layout() {
+  let src = "```\n:one 1\n```\n";
+  let mut pod = Pod::from_string(src, 1024, 1024).unwrap();
+  assert_eq!(pod.revisions(), vec![1]);
+  assert!(!pod.undo());
+  pod.load("Two.\n\n```\n:one 2\n:two 2\n```\n", 1024).unwrap();
+  let moved = "```\n```\nTwo.\n\n```\n:one 2\n:two 2\n```\n";
+  assert_eq!(pod.to_string(), Ok(moved.to_string()));
+  assert!(pod.undo());
+  assert!(pod.undo());
+  let restored = "```\n:one 1\n```\nTwo.\n\n```\n```\n";
+  assert_eq!(pod.to_string(), Ok(restored.to_string()));
+  assert!(pod.redo());
+  assert!(pod.redo());
+  assert_eq!(pod.to_string(), Ok(moved.to_string()));
+}
@@ -0,0 +1,250 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+
+/// How many words may be inlined and rewrites made in one definition.
+const FUEL: usize = 4096;
+
+/// Rewrites a definition into a cheaper one that behaves the same on a
+/// stack with enough values. Library words are inlined, except where
+/// they would recurse or a rewrite rule might apply to them, and known
+/// patterns are simplified, inside blocks as well as out.
+struct Optimizer<'a> {
+  heap: &'a mut Heap,
+  tab: &'a Library,
+  rewrites: &'a Rewrites,
+  active: HashSet<Rc<str>>,
+  fuel: usize,
+}
+
+impl<'a> Optimizer<'a> {
+  fn term(&mut self, root: Gc) -> Result<Gc> {
+    let mut dst = Vec::new();
+    for item in self.heap.get_items(root)?.into_iter() {
+      let (word, block) = match self.heap.get_ref(item)? {
+        &Object::Word(ref key) if self.is_inlined(key) => {
+          (Some(key.clone()), None)
+        }
+        &Object::Block(body) => (None, Some(body)),
+        _ => (None, None),
+      };
+      if let Some(key) = word {
+        let binding = self.tab[&key];
+        self.fuel -= 1;
+        self.active.insert(key.clone());
+        let body = self.term(binding);
+        self.active.remove(&key);
+        for item in self.heap.get_items(body?)?.into_iter() {
+          self.push(&mut dst, item)?;
+        }
+      } else if let Some(body) = block {
+        let body = self.term(body)?;
+        let block = self.heap.new_block(body)?;
+        self.push(&mut dst, block)?;
+      } else {
+        self.push(&mut dst, item)?;
+      }
+    }
+    return self.heap.new_items(&dst);
+  }
+
+  fn is_inlined(&self, key: &Rc<str>) -> bool {
+    return self.fuel > 0 &&
+      self.tab.contains_key(key) &&
+      !self.active.contains(key) &&
+      !self.rewrites.iter().any(|x| &x.head == key);
+  }
+
+  /// Returns the body of a block, or `None` for any other object.
+  fn block(&self, pointer: Gc) -> Result<Option<Gc>> {
+    match self.heap.get_ref(pointer)? {
+      &Object::Block(body) => {
+        return Ok(Some(body));
+      }
+      _ => {
+        return Ok(None);
+      }
+    }
+  }
+
+  fn opcode(&self, pointer: Gc) -> Result<Option<Opcode>> {
+    match self.heap.get_ref(pointer)? {
+      &Object::Opcode(opcode) => {
+        return Ok(Some(opcode));
+      }
+      _ => {
+        return Ok(None);
+      }
+    }
+  }
+
+  /// Appends an item to an optimized term, simplifying the end of it:
+  /// `[A] a` runs `A` in place, `copy drop` and `swap swap` vanish,
+  /// and opcodes applied to literal blocks are done ahead of time.
+  fn push(&mut self, dst: &mut Vec<Gc>, item: Gc) -> Result<()> {
+    let opcode = match self.opcode(item)? {
+      Some(opcode) if self.fuel > 0 => opcode,
+      _ => {
+        dst.push(item);
+        return Ok(());
+      }
+    };
+    let len = dst.len();
+    let top = match len {
+      0 => None,
+      _ => self.block(dst[len - 1])?,
+    };
+    let below = match len {
+      0 | 1 => None,
+      _ => self.block(dst[len - 2])?,
+    };
+    let previous = match len {
+      0 => None,
+      _ => self.opcode(dst[len - 1])?,
+    };
+    self.fuel -= 1;
+    match (opcode, below, top, previous) {
+      (Opcode::App, _, Some(body), _) => {
+        dst.pop();
+        for item in self.heap.get_items(body)?.into_iter() {
+          self.push(dst, item)?;
+        }
+      }
+      (Opcode::Box, _, Some(_), _) => {
+        let value = dst.pop().expect("top");
+        let block = self.heap.new_block(value)?;
+        dst.push(block);
+      }
+      (Opcode::Cat, Some(lhs), Some(rhs), _) => {
+        dst.pop();
+        dst.pop();
+        let mut body = Vec::new();
+        let mut items = self.heap.get_items(lhs)?;
+        items.extend(self.heap.get_items(rhs)?);
+        for item in items.into_iter() {
+          self.push(&mut body, item)?;
+        }
+        let body = self.heap.new_items(&body)?;
+        let block = self.heap.new_block(body)?;
+        dst.push(block);
+      }
+      (Opcode::Copy, _, Some(_), _) => {
+        let value = dst[len - 1];
+        dst.push(value);
+      }
+      (Opcode::Drop, _, Some(_), _) |
+      (Opcode::Drop, _, _, Some(Opcode::Copy)) |
+      (Opcode::Swap, _, _, Some(Opcode::Swap)) => {
+        dst.pop();
+      }
+      (Opcode::Swap, Some(_), Some(_), _) => {
+        dst.swap(len - 2, len - 1);
+      }
+      _ => {
+        self.fuel += 1;
+        dst.push(item);
+      }
+    }
+    return Ok(());
+  }
+}
+
+impl Pod {
+  /// Computes an optimized body for every definition and keeps it
+  /// alongside the original until the library next changes. Words in
+  /// blocks stay late-bound in the originals, so reduction still uses
+  /// those.
+  pub fn optimize(&mut self) -> Result<()> {
+    let mut optimized = HashMap::new();
+    for (key, value) in self.tab.iter() {
+      let mut optimizer = Optimizer {
+        heap: &mut self.heap,
+        tab: &self.tab,
+        rewrites: &self.rewrites,
+        active: HashSet::new(),
+        fuel: FUEL,
+      };
+      optimizer.active.insert(key.clone());
+      let body = optimizer.term(*value)?;
+      optimized.insert(key.clone(), body);
+    }
+    self.optimized = Some(optimized);
+    return self.collect();
+  }
+
+  /// Optimizes a term against the current library without reducing
+  /// it.
+  pub fn optimize_term(&mut self, src: &str) -> Result<String> {
+    let root = parse(src, &mut self.heap)?;
+    let mut optimizer = Optimizer {
+      heap: &mut self.heap,
+      tab: &self.tab,
+      rewrites: &self.rewrites,
+      active: HashSet::new(),
+      fuel: FUEL,
+    };
+    let target = optimizer.term(root)?;
+    let mut dst = String::new();
+    quote(target, &self.heap, &mut dst)?;
+    self.collect()?;
+    return Ok(dst);
+  }
+
+  /// Quotes the optimized body of a key, if the library has been
+  /// optimized since it last changed.
+  pub fn optimized(&self, key: &str) -> Option<String> {
+    let value = self.optimized.as_ref()?.get(key)?;
+    let mut dst = String::new();
+    quote(*value, &self.heap, &mut dst).ok()?;
+    return Some(dst);
+  }
+}
+
+#[test]
+fn optimize() {
+  let src = "```
+:pair b f b f c
+:both [pair]
+:noop [[A] a f f d e]
+:loop [loop] a
+```
+";
+  let mut pod = Pod::from_string(src, 4096, 4096).unwrap();
+  assert_eq!(pod.optimized("both"), None);
+  pod.optimize().unwrap();
+  assert_eq!(pod.optimized("both"), Some("[b f b f c]".to_string()));
+  assert_eq!(pod.optimized("noop"), Some("[A]".to_string()));
+  assert_eq!(pod.optimized("loop"), Some("loop".to_string()));
+  assert!(pod.eval(":pair f", 4096).is_ok());
+  assert_eq!(pod.optimized("both"), None);
+}
+
+#[test]
+fn optimize_prelude() {
+  let time = 1 << 16;
+  let src = include_str!("../../lib/default.md");
+  let mut pod = Pod::from_string(src, 1 << 16, time).unwrap();
+  pod.optimize().unwrap();
+  let keys = pod.keys();
+  let optimized: Vec<String> = keys.iter()
+    .map(|key| pod.optimized(key).unwrap()).collect();
+  for (key, optimized) in keys.iter().zip(optimized.iter()) {
+    let found = pod.equivalent(key, optimized, time);
+    assert_eq!(found, Ok(true), "{} => {}", key, optimized);
+  }
+}
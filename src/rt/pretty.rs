@@ -0,0 +1,416 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+
+/// A node of the layout tree.
+enum Node<'a> {
+  Atom(&'a str),
+  /// A comment and the number of spaces written before it, kept so
+  /// that aligned comments stay aligned.
+  Comment(&'a str, usize),
+  Block(Vec<Node<'a>>),
+  Hint(&'a str, Vec<Node<'a>>),
+  /// A line break written in the source.
+  Break,
+}
+
+impl<'a> Node<'a> {
+  fn is_line_comment(&self) -> bool {
+    match self {
+      &Node::Comment(text, _) => text.starts_with('#'),
+      _ => false,
+    }
+  }
+
+  fn is_break(&self) -> bool {
+    match self {
+      &Node::Break => true,
+      _ => false,
+    }
+  }
+
+  /// Returns the spaces wanted between the node and the one before it
+  /// on the same line.
+  fn space(&self) -> usize {
+    match self {
+      &Node::Comment(_, space) => space.max(1),
+      _ => 1,
+    }
+  }
+
+  /// Returns the node written on one line, if it fits on one.
+  fn flat(&self) -> Option<String> {
+    match self {
+      &Node::Atom(text) | &Node::Comment(text, _) => {
+        return Some(text.to_string());
+      }
+      &Node::Block(ref nodes) => {
+        if nodes.iter().any(|x| x.is_line_comment()) {
+          return None;
+        }
+        return Some(format!("[{}]", flat(nodes)?));
+      }
+      &Node::Hint(name, ref nodes) => {
+        if nodes.iter().any(|x| x.is_line_comment()) {
+          return None;
+        }
+        if nodes.is_empty() {
+          return Some(format!("({})", name));
+        }
+        return Some(format!("({} {})", name, flat(nodes)?));
+      }
+      &Node::Break => {
+        return None;
+      }
+    }
+  }
+}
+
+/// Writes a sequence of nodes on one line, if it fits on one. Only the
+/// last node may be a line comment, and none may be a line break.
+fn flat(nodes: &[Node]) -> Option<String> {
+  let mut buf = String::new();
+  for (index, node) in nodes.iter().enumerate() {
+    if node.is_line_comment() && index + 1 < nodes.len() {
+      return None;
+    }
+    if !buf.is_empty() {
+      buf.push_str(&" ".repeat(node.space()));
+    }
+    buf.push_str(&node.flat()?);
+  }
+  return Some(buf);
+}
+
+/// Builds the layout tree of some source, noting where it breaks lines
+/// between the items of a sequence.
+fn build(src: &str) -> Result<Vec<Node<'_>>> {
+  let mut build = Vec::new();
+  let mut stack: Vec<(Vec<Node>, Option<&str>)> = Vec::new();
+  for (start, token) in spans(src)? {
+    let before = &src[..start];
+    let gap = &before[before.trim_end().len()..];
+    let is_item = match token {
+      Token::Close | Token::HintClose => false,
+      _ => true,
+    };
+    if is_item && gap.contains('\n') && !build.is_empty() {
+      build.push(Node::Break);
+    }
+    match token {
+      Token::Open => {
+        stack.push((build, None));
+        build = Vec::new();
+      }
+      Token::HintOpen(name) => {
+        stack.push((build, Some(name)));
+        build = Vec::new();
+      }
+      Token::Close | Token::HintClose => {
+        let (mut prev, hint) = stack.pop().ok_or(Error::Syntax)?;
+        match (hint, token) {
+          (None, Token::Close) => {
+            prev.push(Node::Block(build));
+          }
+          (Some(name), Token::HintClose) => {
+            prev.push(Node::Hint(name, build));
+          }
+          _ => {
+            return Err(Error::Syntax);
+          }
+        }
+        build = prev;
+      }
+      Token::Atom(text) | Token::Text(text) => {
+        build.push(Node::Atom(text));
+      }
+      Token::Comment(text) => {
+        let space = match gap.rfind('\n') {
+          Some(index) => gap.len() - index - 1,
+          None => gap.len(),
+        };
+        build.push(Node::Comment(text, space));
+      }
+    }
+  }
+  if !stack.is_empty() {
+    return Err(Error::Syntax);
+  }
+  return Ok(build);
+}
+
+/// Lays out nodes within a width, filling lines greedily and keeping
+/// the source's own line breaks. A block too wide for its line starts
+/// a new one and breaks after its `[`, with its contents aligned one
+/// column in.
+struct Printer {
+  buf: String,
+  col: usize,
+  width: usize,
+  is_line_start: bool,
+  must_break: bool,
+}
+
+impl Printer {
+  fn new(width: usize) -> Self {
+    Printer {
+      buf: String::new(),
+      col: 0,
+      width: width,
+      is_line_start: true,
+      must_break: false,
+    }
+  }
+
+  fn newline(&mut self, indent: usize) {
+    self.buf.push('\n');
+    self.buf.push_str(&" ".repeat(indent));
+    self.col = indent;
+    self.is_line_start = true;
+    self.must_break = false;
+  }
+
+  fn push(&mut self, text: &str) {
+    self.buf.push_str(text);
+    self.col += text.chars().count();
+    self.is_line_start = false;
+  }
+
+  fn nodes(&mut self, nodes: &[Node], indent: usize) {
+    for node in nodes.iter() {
+      if node.is_break() {
+        if !self.is_line_start {
+          self.newline(indent);
+        }
+        continue;
+      }
+      self.node(node, indent);
+    }
+  }
+
+  fn node(&mut self, node: &Node, indent: usize) {
+    if let Some(text) = node.flat() {
+      let length = text.chars().count();
+      let is_breakable = match node {
+        &Node::Block(_) | &Node::Hint(_, _) => true,
+        _ => false,
+      };
+      let fits = |col: usize| col + length <= self.width;
+      let space = if self.is_line_start {
+        0
+      } else if fits(self.col + node.space()) {
+        node.space()
+      } else {
+        1
+      };
+      if !self.must_break && fits(self.col + space) {
+        self.push(&" ".repeat(space));
+        self.push(&text);
+        self.must_break = node.is_line_comment();
+        return;
+      }
+      if !is_breakable || fits(indent) {
+        if !self.is_line_start {
+          self.newline(indent);
+        }
+        self.push(&text);
+        self.must_break = node.is_line_comment();
+        return;
+      }
+    }
+    if !self.is_line_start {
+      self.newline(indent);
+    }
+    let start = self.col;
+    let (open, nodes, close) = match node {
+      &Node::Block(ref nodes) => ("[".to_string(), nodes, "]"),
+      &Node::Hint(name, ref nodes) => (format!("({}", name), nodes, ")"),
+      _ => {
+        return;
+      }
+    };
+    self.push(&open);
+    if let &Node::Hint(_, _) = node {
+      self.push(" ");
+    }
+    self.is_line_start = true;
+    let inner = self.col;
+    self.nodes(nodes, inner);
+    if self.must_break {
+      self.newline(start);
+    }
+    self.push(close);
+  }
+}
+
+/// Formats a Sundial term to fit within a width where it can.
+pub fn format_term(src: &str, width: usize) -> Result<String> {
+  let nodes = build(src)?;
+  let mut printer = Printer::new(width);
+  printer.nodes(&nodes, 0);
+  check(src, &printer.buf)?;
+  return Ok(printer.buf);
+}
+
+/// Formats a statement of a pod file: a definition, a deletion or a
+/// term. Statements too wide for one line, or written on several,
+/// continue on indented lines.
+fn format_statement(src: &str, width: usize) -> Result<String> {
+  if let Some(data) = POD_DELETE_REGEX.captures(src) {
+    if data.get(0).expect("all").as_str().len() == src.trim_end().len() {
+      return Ok(src.trim().to_string());
+    }
+  }
+  let (head, body, is_broken) = match POD_INSERT_REGEX.captures(src) {
+    Some(data) => {
+      let arity = match data.get(2) {
+        Some(arity) => format!("/{}", arity.as_str()),
+        None => String::new(),
+      };
+      let head = format!(
+        ":{}{}{}",
+        data.get(1).expect("key").as_str(),
+        arity,
+        data.get(3).expect("force").as_str());
+      let value = data.get(4).expect("value");
+      let gap = &src[data.get(3).expect("force").end()..value.start()];
+      (head, value.as_str(), gap.contains('\n'))
+    }
+    None => {
+      (String::new(), src, false)
+    }
+  };
+  let mut nodes = build(body)?;
+  if is_broken && !nodes.is_empty() {
+    nodes.insert(0, Node::Break);
+  }
+  let mut printer = Printer::new(width);
+  if !head.is_empty() {
+    printer.push(&head);
+    match flat(&nodes) {
+      Some(ref text) if head.len() + 1 + text.chars().count() <= width => {
+        printer.push(" ");
+        printer.push(text);
+      }
+      _ if nodes.iter().any(|x| x.is_break()) => {
+        printer.nodes(&nodes, 2);
+      }
+      _ => {
+        printer.newline(2);
+        printer.nodes(&nodes, 2);
+      }
+    }
+  } else {
+    printer.nodes(&nodes, 2);
+  }
+  let dst = printer.buf;
+  check(body, &dst[head.len()..])?;
+  return Ok(dst);
+}
+
+/// Formats the code blocks of a literate pod file, leaving prose and
+/// other blocks as they are. Definitions keep their order, since each
+/// is reduced against the ones before it.
+pub fn format_pod(src: &str, width: usize) -> Result<String> {
+  let mut dst = String::new();
+  let mut body = String::new();
+  let mut fence: Option<bool> = None;
+  for line in src.lines() {
+    let info = FENCE_REGEX.captures(line)
+      .map(|x| x.get(1).expect("info").as_str());
+    match fence {
+      None => {
+        if let Some(info) = info {
+          fence = Some(is_code_fence(info));
+        }
+        dst.push_str(line);
+        dst.push('\n');
+      }
+      Some(true) if info.is_none() => {
+        body.push_str(line);
+        body.push('\n');
+      }
+      Some(true) => {
+        format_block(&body, width, &mut dst)?;
+        body.clear();
+        dst.push_str(line);
+        dst.push('\n');
+        fence = None;
+      }
+      Some(false) => {
+        if info.is_some() {
+          fence = None;
+        }
+        dst.push_str(line);
+        dst.push('\n');
+      }
+    }
+  }
+  if let Some(true) = fence {
+    format_block(&body, width, &mut dst)?;
+  }
+  return Ok(dst);
+}
+
+fn format_block(src: &str, width: usize, dst: &mut String) -> Result<()> {
+  for (_, statement) in split_statements(src).iter() {
+    if !statement.is_empty() {
+      dst.push_str(&format_statement(statement, width)?);
+    }
+    dst.push('\n');
+  }
+  return Ok(());
+}
+
+/// Checks that formatting changed only the whitespace of some source,
+/// by comparing the tokens of both versions.
+fn check(before: &str, after: &str) -> Result<()> {
+  if tokenize(before)? != tokenize(after)? {
+    return Err(Error::Bug);
+  }
+  return Ok(());
+}
+
+#[test]
+fn format() {
+  let check = |source, width, expected| {
+    println!("{} => {}", source, expected);
+    assert_eq!(expected, &format_term(source, width).unwrap());
+  };
+  check("[ A  B ]  [C\n]", 72, "[A B] [C]");
+  check("(assert  block ) a", 72, "(assert block) a");
+  check("A B C D E", 5, "A B C\nD E");
+  check("[A B C D E] F", 5, "[A B\n C D\n E] F");
+  check("A # note\nB", 72, "A # note\nB");
+  check("[A # note\n]", 72, "[A # note\n]");
+  check("A {note} \"x  y\" b\"z\"", 72, "A {note} \"x  y\" b\"z\"");
+  assert!(format_term("[A", 72).is_err());
+  let src = "# Title\n\n```\n:pair   b f\n  b f c\n:long [AAAA BBBB CCCC DDDD]\n\n~pair\n```\n";
+  let dst = "# Title\n\n```\n:pair b f\n  b f c\n:long\n  [AAAA BBBB CCCC\n   DDDD]\n\n~pair\n```\n";
+  assert_eq!(dst, &format_pod(src, 18).unwrap());
+  assert_eq!(dst, &format_pod(dst, 18).unwrap());
+  let src = "```\n:pair\n  b f  # B A\n  c    # A B\n```\n";
+  let dst = "```\n:pair\n  b f # B A\n  c # A B\n```\n";
+  assert_eq!(src, &format_pod(src, 72).unwrap());
+  assert_eq!(dst, &format_pod(src, 11).unwrap());
+  let src = "```\n:big 18446744073709551615\n```\n";
+  assert_eq!(src, &format_pod(src, 72).unwrap());
+  assert!(format_term("[A", 72).is_err());
+  let src = include_str!("../../lib/default.md");
+  assert_eq!(src, &format_pod(src, 72).unwrap());
+}
@@ -0,0 +1,602 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use super::equal::reduce_fully;
+use std::fmt;
+
+/// The most fresh variables `Abs` and `Prop` introduce.
+const MAX_VARIABLES: usize = 3;
+
+/// The rules of the judgment system in the README, and `Assume`, which
+/// concludes any judgment already in the context.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+  Assume,
+  Eval,
+  App,
+  Abs,
+  Prop,
+  Loop,
+}
+
+/// A derivation of `context :- goal` by a rule from its premises.
+/// Terms are quoted in normal layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derivation {
+  pub context: Vec<String>,
+  pub goal: String,
+  pub rule: Rule,
+  pub premises: Vec<Derivation>,
+}
+
+impl Derivation {
+  fn write(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+    writeln!(f, "{:width$}{} :- {}    {:?}",
+             "", self.context.join(", "), self.goal, self.rule,
+             width = depth * 2)?;
+    for premise in self.premises.iter() {
+      premise.write(f, depth + 1)?;
+    }
+    return Ok(());
+  }
+}
+
+impl fmt::Display for Derivation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return self.write(f, 0);
+  }
+}
+
+/// Takes terms apart and compares them for the prover and the checker.
+/// Every reduction draws on one quota.
+struct Terms<'a> {
+  pod: &'a mut Pod,
+  quota: u64,
+}
+
+impl<'a> Terms<'a> {
+  fn items(&mut self, src: &str) -> Result<Vec<Gc>> {
+    let root = parse(src, &mut self.pod.heap)?;
+    return self.pod.heap.get_items(root);
+  }
+
+  fn text(&mut self, items: &[Gc]) -> Result<String> {
+    let root = self.pod.heap.new_items(items)?;
+    let mut dst = String::new();
+    quote(root, &self.pod.heap, &mut dst)?;
+    return Ok(dst);
+  }
+
+  /// Quotes a term in normal layout, so equal terms give equal text.
+  fn canonical(&mut self, src: &str) -> Result<String> {
+    let items = self.items(src)?;
+    return self.text(&items);
+  }
+
+  /// Returns the body of a term that is a single block.
+  fn quoted(&mut self, src: &str) -> Result<Option<Vec<Gc>>> {
+    let items = self.items(src)?;
+    if items.len() != 1 {
+      return Ok(None);
+    }
+    match self.pod.heap.get_ref(items[0])? {
+      &Object::Block(body) => {
+        return Ok(Some(self.pod.heap.get_items(body)?));
+      }
+      _ => {
+        return Ok(None);
+      }
+    }
+  }
+
+  /// Takes a term `[X o]` apart into `X`, if `o` is the given opcode.
+  fn ends_with(
+    &mut self, src: &str, opcode: Opcode) -> Result<Option<Vec<Gc>>> {
+    let mut body = match self.quoted(src)? {
+      Some(body) => body,
+      None => {
+        return Ok(None);
+      }
+    };
+    match body.last() {
+      Some(&last) => match self.pod.heap.get_ref(last)? {
+        &Object::Opcode(found) if found == opcode => {
+          body.pop();
+          return Ok(Some(body));
+        }
+        _ => {
+          return Ok(None);
+        }
+      },
+      None => {
+        return Ok(None);
+      }
+    }
+  }
+
+  /// Takes a proposition `[X h]` apart into `X`.
+  fn proposition(&mut self, src: &str) -> Result<Option<String>> {
+    match self.ends_with(src, Opcode::Prop)? {
+      Some(body) => {
+        return Ok(Some(self.text(&body)?));
+      }
+      None => {
+        return Ok(None);
+      }
+    }
+  }
+
+  /// Takes an implication `[[F] [G] g]` apart into `F` and `G`.
+  fn implication(&mut self, src: &str) -> Result<Option<(String, String)>> {
+    let body = match self.ends_with(src, Opcode::Forall)? {
+      Some(ref body) if body.len() == 2 => self.text(body)?,
+      _ => {
+        return Ok(None);
+      }
+    };
+    let items = self.items(&body)?;
+    let mut parts = Vec::new();
+    for item in items.into_iter() {
+      let text = self.text(&[item])?;
+      match self.quoted(&text)? {
+        Some(body) => {
+          parts.push(self.text(&body)?);
+        }
+        None => {
+          return Ok(None);
+        }
+      }
+    }
+    let snd = parts.pop().expect("snd");
+    let fst = parts.pop().expect("fst");
+    return Ok(Some((fst, snd)));
+  }
+
+  /// Splits a term into blocks followed by `suffix`, returning the
+  /// blocks, if it has that shape.
+  fn strip_suffix(
+    &mut self, src: &str, suffix: &str) -> Result<Option<Vec<String>>> {
+    let items = self.items(src)?;
+    let suffix_items = self.items(suffix)?;
+    if items.len() < suffix_items.len() {
+      return Ok(None);
+    }
+    let split = items.len() - suffix_items.len();
+    let tail = self.text(&items[split..])?;
+    let suffix = self.text(&suffix_items)?;
+    if tail != suffix {
+      return Ok(None);
+    }
+    let mut prefix = Vec::new();
+    for item in items[..split].iter() {
+      let text = self.text(&[*item])?;
+      if self.quoted(&text)?.is_none() {
+        return Ok(None);
+      }
+      prefix.push(text);
+    }
+    return Ok(Some(prefix));
+  }
+
+  fn normal(&mut self, src: &str) -> Result<String> {
+    let root = parse(src, &mut self.pod.heap)?;
+    let target = reduce_fully(self.pod, root, &mut self.quota)?;
+    let items = self.pod.heap.get_items(target)?;
+    return self.text(&items);
+  }
+
+  /// Returns true if two terms are equal by reduction, or are blocks
+  /// whose bodies are.
+  fn is_equal(&mut self, lhs: &str, rhs: &str) -> Result<bool> {
+    if self.normal(lhs)? == self.normal(rhs)? {
+      return Ok(true);
+    }
+    match (self.quoted(lhs)?, self.quoted(rhs)?) {
+      (Some(lhs), Some(rhs)) => {
+        let lhs = self.text(&lhs)?;
+        let rhs = self.text(&rhs)?;
+        return Ok(self.normal(&lhs)? == self.normal(&rhs)?);
+      }
+      _ => {
+        return Ok(false);
+      }
+    }
+  }
+
+  /// Returns the word of a block holding only a placeholder, like
+  /// `[X1]`.
+  fn placeholder(&mut self, src: &str) -> Result<Option<Rc<str>>> {
+    let body = match self.quoted(src)? {
+      Some(ref body) if body.len() == 1 => body[0],
+      _ => {
+        return Ok(None);
+      }
+    };
+    match self.pod.heap.get_ref(body)? {
+      &Object::Word(ref key) if !KEY_REGEX.is_match(key) => {
+        return Ok(Some(key.clone()));
+      }
+      _ => {
+        return Ok(None);
+      }
+    }
+  }
+
+  /// Joins terms into one in normal layout.
+  fn join(&mut self, parts: &[&str]) -> Result<String> {
+    return self.canonical(&parts.join(" "));
+  }
+}
+
+/// Returns true if a word occurs in any of the terms.
+fn occurs(name: &str, terms: &[&str]) -> bool {
+  return terms.iter().any(|term| {
+    term.split(|x: char| x.is_whitespace() || "[]()".contains(x))
+      .any(|word| word == name)
+  });
+}
+
+/// Returns `count` blocks of placeholder words that occur in none of
+/// the terms.
+fn fresh(count: usize, terms: &[&str]) -> Vec<String> {
+  let mut dst = Vec::new();
+  let mut index = 0;
+  while dst.len() < count {
+    index += 1;
+    let name = format!("X{}", index);
+    if !occurs(&name, terms) {
+      dst.push(format!("[{}]", name));
+    }
+  }
+  return dst;
+}
+
+struct Prover<'a> {
+  terms: Terms<'a>,
+}
+
+impl<'a> Prover<'a> {
+  fn node(
+    context: &[String],
+    goal: &str,
+    rule: Rule,
+    premises: Vec<Derivation>) -> Derivation {
+    return Derivation {
+      context: context.to_vec(),
+      goal: goal.to_string(),
+      rule: rule,
+      premises: premises,
+    };
+  }
+
+  fn prove(
+    &mut self,
+    context: &[String],
+    goal: &str,
+    depth: usize) -> Result<Option<Derivation>> {
+    if context.iter().any(|x| x == goal) {
+      return Ok(Some(Prover::node(context, goal, Rule::Assume, vec![])));
+    }
+    if depth == 0 {
+      return Ok(None);
+    }
+    for hypothesis in context.iter() {
+      if self.terms.is_equal(hypothesis, goal)? {
+        let premise = Prover::node(context, hypothesis, Rule::Assume, vec![]);
+        return Ok(Some(Prover::node(context, goal, Rule::Eval, vec![premise])));
+      }
+    }
+    if let Some(inner) = self.terms.proposition(goal)? {
+      if let Some(inner) = self.terms.proposition(&inner)? {
+        if self.terms.quoted(&inner)?.is_some() {
+          return Ok(Some(Prover::node(context, goal, Rule::Loop, vec![])));
+        }
+      }
+      if let Some((fst, snd)) = self.terms.implication(&inner)? {
+        let found = self.introduce(context, goal, &fst, &snd, true, depth)?;
+        if found.is_some() {
+          return Ok(found);
+        }
+      }
+    }
+    if let Some((fst, snd)) = self.terms.implication(goal)? {
+      let found = self.introduce(context, goal, &fst, &snd, false, depth)?;
+      if found.is_some() {
+        return Ok(found);
+      }
+    }
+    for hypothesis in context.iter() {
+      let (fst, snd) = match self.terms.implication(hypothesis)? {
+        Some(parts) => parts,
+        None => {
+          continue;
+        }
+      };
+      let prefix = match self.terms.strip_suffix(goal, &snd)? {
+        Some(prefix) => prefix.join(" "),
+        None => {
+          continue;
+        }
+      };
+      let instance = self.terms.join(&[&prefix, &fst])?;
+      if let Some(premise) = self.prove(context, &instance, depth - 1)? {
+        let rule = Prover::node(context, hypothesis, Rule::Assume, vec![]);
+        let premises = vec![rule, premise];
+        return Ok(Some(Prover::node(context, goal, Rule::App, premises)));
+      }
+    }
+    let normal = self.terms.normal(goal)?;
+    if normal != goal {
+      if let Some(premise) = self.prove(context, &normal, depth - 1)? {
+        return Ok(Some(Prover::node(context, goal, Rule::Eval, vec![premise])));
+      }
+    }
+    return Ok(None);
+  }
+
+  /// Tries `Abs`, or `Prop` if `is_prop`, for an implication from
+  /// `fst` to `snd`, over each number of fresh variables in turn.
+  fn introduce(
+    &mut self,
+    context: &[String],
+    goal: &str,
+    fst: &str,
+    snd: &str,
+    is_prop: bool,
+    depth: usize) -> Result<Option<Derivation>> {
+    for count in 0..MAX_VARIABLES + 1 {
+      let mut terms: Vec<&str> = context.iter().map(|x| &**x).collect();
+      terms.push(fst);
+      terms.push(snd);
+      let variables = fresh(count, &terms).join(" ");
+      let antecedent = self.terms.join(&[&variables, fst])?;
+      let premise = self.terms.join(&["[", &antecedent, "h]"])?;
+      let consequent = self.terms.join(&[&variables, snd])?;
+      let consequent = match is_prop {
+        true => self.terms.join(&["[", &consequent, "h]"])?,
+        false => consequent,
+      };
+      let fst_proof = match self.prove(context, &premise, depth - 1)? {
+        Some(proof) => proof,
+        None => {
+          continue;
+        }
+      };
+      let mut inner = context.to_vec();
+      inner.push(antecedent);
+      if let Some(snd_proof) = self.prove(&inner, &consequent, depth - 1)? {
+        let rule = if is_prop { Rule::Prop } else { Rule::Abs };
+        let premises = vec![fst_proof, snd_proof];
+        return Ok(Some(Prover::node(context, goal, rule, premises)));
+      }
+    }
+    return Ok(None);
+  }
+}
+
+struct Checker<'a> {
+  terms: Terms<'a>,
+}
+
+impl<'a> Checker<'a> {
+  fn check(&mut self, derivation: &Derivation) -> Result<bool> {
+    let mut context = Vec::new();
+    for term in derivation.context.iter() {
+      context.push(self.terms.canonical(term)?);
+    }
+    let goal = self.terms.canonical(&derivation.goal)?;
+    let premises = &derivation.premises;
+    let mut premise_contexts = Vec::new();
+    let mut premise_goals = Vec::new();
+    for premise in premises.iter() {
+      let mut terms = Vec::new();
+      for term in premise.context.iter() {
+        terms.push(self.terms.canonical(term)?);
+      }
+      premise_contexts.push(terms);
+      premise_goals.push(self.terms.canonical(&premise.goal)?);
+    }
+    let is_step = match derivation.rule {
+      Rule::Assume => {
+        premises.is_empty() && context.contains(&goal)
+      }
+      Rule::Eval => {
+        premises.len() == 1 &&
+          premise_contexts[0] == context &&
+          self.terms.is_equal(&premise_goals[0], &goal)?
+      }
+      Rule::App => {
+        premises.len() == 2 &&
+          premise_contexts.iter().all(|x| x == &context) &&
+          self.is_app(&premise_goals[0], &premise_goals[1], &goal)?
+      }
+      Rule::Abs | Rule::Prop => {
+        let is_prop = derivation.rule == Rule::Prop;
+        premises.len() == 2 &&
+          premise_contexts[0] == context &&
+          premise_contexts[1].len() == context.len() + 1 &&
+          premise_contexts[1][..context.len()] == context[..] &&
+          self.is_introduction(
+            &context,
+            &premise_goals[0],
+            &premise_contexts[1][context.len()],
+            &premise_goals[1],
+            &goal,
+            is_prop)?
+      }
+      Rule::Loop => {
+        premises.is_empty() && self.is_loop(&goal)?
+      }
+    };
+    if !is_step {
+      return Ok(false);
+    }
+    for premise in premises.iter() {
+      if !self.check(premise)? {
+        return Ok(false);
+      }
+    }
+    return Ok(true);
+  }
+
+  fn is_app(&mut self, rule: &str, instance: &str, goal: &str) -> Result<bool> {
+    let (fst, snd) = match self.terms.implication(rule)? {
+      Some(parts) => parts,
+      None => {
+        return Ok(false);
+      }
+    };
+    let prefix = match self.terms.strip_suffix(instance, &fst)? {
+      Some(prefix) => prefix.join(" "),
+      None => {
+        return Ok(false);
+      }
+    };
+    return Ok(self.terms.join(&[&prefix, &snd])? == goal);
+  }
+
+  fn is_introduction(
+    &mut self,
+    context: &[String],
+    premise: &str,
+    antecedent: &str,
+    consequent: &str,
+    goal: &str,
+    is_prop: bool) -> Result<bool> {
+    let implication = match is_prop {
+      true => match self.terms.proposition(goal)? {
+        Some(inner) => inner,
+        None => {
+          return Ok(false);
+        }
+      },
+      false => goal.to_string(),
+    };
+    let (fst, snd) = match self.terms.implication(&implication)? {
+      Some(parts) => parts,
+      None => {
+        return Ok(false);
+      }
+    };
+    let variables = match self.terms.strip_suffix(antecedent, &fst)? {
+      Some(variables) => variables,
+      None => {
+        return Ok(false);
+      }
+    };
+    let mut terms: Vec<&str> = context.iter().map(|x| &**x).collect();
+    terms.push(&fst);
+    terms.push(&snd);
+    let mut names = HashSet::new();
+    for variable in variables.iter() {
+      let is_fresh = match self.terms.placeholder(variable)? {
+        Some(name) => !occurs(&name, &terms) && names.insert(name),
+        None => false,
+      };
+      if !is_fresh {
+        return Ok(false);
+      }
+    }
+    let variables = variables.join(" ");
+    let expected = self.terms.join(&[&variables, &snd])?;
+    let expected = match is_prop {
+      true => self.terms.join(&["[", &expected, "h]"])?,
+      false => expected,
+    };
+    let antecedent = self.terms.join(&[&variables, &fst])?;
+    let expected_premise = self.terms.join(&["[", &antecedent, "h]"])?;
+    return Ok(premise == expected_premise && consequent == expected);
+  }
+
+  fn is_loop(&mut self, goal: &str) -> Result<bool> {
+    if let Some(inner) = self.terms.proposition(goal)? {
+      if let Some(inner) = self.terms.proposition(&inner)? {
+        return Ok(self.terms.quoted(&inner)?.is_some());
+      }
+    }
+    return Ok(false);
+  }
+}
+
+impl Pod {
+  /// Searches for a derivation of `context :- goal` at most `depth`
+  /// rules deep, by the rules of the judgment system in the README.
+  /// Every reduction the search makes shares `time_quota`.
+  pub fn prove(
+    &mut self,
+    context: &[&str],
+    goal: &str,
+    depth: usize,
+    time_quota: u64) -> Result<Option<Derivation>> {
+    let dst = {
+      let mut prover = Prover {
+        terms: Terms { pod: self, quota: time_quota },
+      };
+      let mut terms = Vec::new();
+      for term in context.iter() {
+        terms.push(prover.terms.canonical(term)?);
+      }
+      let goal = prover.terms.canonical(goal)?;
+      prover.prove(&terms, &goal, depth)
+    };
+    self.collect()?;
+    return dst;
+  }
+
+  /// Returns true if every step of a derivation follows its rule.
+  pub fn check(
+    &mut self, derivation: &Derivation, time_quota: u64) -> Result<bool> {
+    let dst = {
+      let mut checker = Checker {
+        terms: Terms { pod: self, quota: time_quota },
+      };
+      checker.check(derivation)
+    };
+    self.collect()?;
+    return dst;
+  }
+}
+
+#[test]
+fn proof() {
+  let src = "```\n:pair b f b f c\n```\n";
+  let mut pod = Pod::from_string(src, 1 << 16, 1 << 16).unwrap();
+  let time = 1 << 16;
+  let mut prove = |context: &[&str], goal: &str| -> Option<Rule> {
+    let proof = pod.prove(context, goal, 4, time).unwrap()?;
+    assert_eq!(pod.check(&proof, time), Ok(true), "{}", proof);
+    return Some(proof.rule);
+  };
+  assert_eq!(prove(&["[[A] [B] pair]"], "[[[A] [B]]]"), Some(Rule::Eval));
+  assert_eq!(prove(&["[[P] [Q] g]", "P"], "Q"), Some(Rule::App));
+  assert_eq!(prove(&["[[P] [Q] g]", "[A] P"], "[A] Q"), Some(Rule::App));
+  assert_eq!(prove(&["[A h]"], "[[A] [A] g]"), Some(Rule::Abs));
+  assert_eq!(prove(&["[A h]"], "[[[A] [A] g] h]"), Some(Rule::Prop));
+  assert_eq!(prove(&[], "[[[A] h] h]"), Some(Rule::Loop));
+  let goal = "[[A] [A [B] [C] pair e] g]";
+  assert_eq!(prove(&["[A h]"], goal), Some(Rule::Abs));
+  assert_eq!(prove(&[], "[[A] [A] g]"), None);
+  assert_eq!(prove(&["[[P] [Q] g]"], "Q"), None);
+  let bogus = Derivation {
+    context: vec![],
+    goal: "Q".to_string(),
+    rule: Rule::Assume,
+    premises: vec![],
+  };
+  assert_eq!(pod.check(&bogus, time), Ok(false));
+}
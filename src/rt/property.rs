@@ -0,0 +1,215 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+
+/// Lists every well-bracketed term of exactly `size` nodes, counting
+/// each atom and each block as one, built from the given atoms.
+pub fn enumerate(atoms: &[&str], size: usize) -> Vec<String> {
+  let mut terms: Vec<Vec<String>> = vec![vec![String::new()]];
+  for size in 1..size + 1 {
+    let mut dst = Vec::new();
+    for atom in atoms.iter() {
+      for rest in terms[size - 1].iter() {
+        dst.push(join(atom, rest));
+      }
+    }
+    for inner in 0..size {
+      for body in terms[inner].iter() {
+        let block = format!("[{}]", body);
+        for rest in terms[size - 1 - inner].iter() {
+          dst.push(join(&block, rest));
+        }
+      }
+    }
+    terms.push(dst);
+  }
+  return terms.pop().expect("terms");
+}
+
+fn join(fst: &str, snd: &str) -> String {
+  if snd.is_empty() {
+    return fst.to_string();
+  }
+  return format!("{} {}", fst, snd);
+}
+
+/// Draws random well-bracketed terms. The mix of atoms is set by
+/// `atoms`, where an atom listed twice is drawn twice as often; add
+/// library words, numerals, text or hints to it as needed.
+pub struct Generator {
+  /// The nodes in each term, counting each atom and each block as one.
+  pub size: usize,
+  pub atoms: Vec<String>,
+  /// The chance, out of a hundred, that a node is a block.
+  pub block_percent: u64,
+  state: u64,
+}
+
+impl Generator {
+  /// Creates a generator of terms built from the opcodes. The same
+  /// seed always gives the same terms.
+  pub fn new(seed: u64, size: usize) -> Self {
+    let atoms = ["a", "b", "c", "d", "e", "f", "g", "h"];
+    Generator {
+      size: size,
+      atoms: atoms.iter().map(|x| x.to_string()).collect(),
+      block_percent: 25,
+      state: seed.wrapping_mul(0x9e3779b97f4a7c15) | 1,
+    }
+  }
+
+  /// Returns a number below `bound`, by xorshift.
+  fn below(&mut self, bound: u64) -> u64 {
+    self.state ^= self.state << 13;
+    self.state ^= self.state >> 7;
+    self.state ^= self.state << 17;
+    return self.state % bound;
+  }
+
+  /// Draws a term of `size` nodes.
+  pub fn term(&mut self) -> String {
+    let mut dst = String::new();
+    let size = self.size;
+    self.nodes(size, &mut dst);
+    return dst;
+  }
+
+  fn nodes(&mut self, size: usize, buf: &mut String) {
+    let mut size = size;
+    while size > 0 {
+      if !buf.is_empty() && !buf.ends_with('[') {
+        buf.push(' ');
+      }
+      let is_block = self.atoms.is_empty() ||
+        self.below(100) < self.block_percent;
+      if is_block {
+        let inner = self.below(size as u64) as usize;
+        buf.push('[');
+        self.nodes(inner, buf);
+        buf.push(']');
+        size -= inner + 1;
+      } else {
+        let index = self.below(self.atoms.len() as u64) as usize;
+        buf.push_str(&self.atoms[index]);
+        size -= 1;
+      }
+    }
+  }
+}
+
+/// A law every term should obey.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Property {
+  /// Parsing the quotation of a term gives the same term.
+  Quote,
+  /// Reducing a term twice gives the same result in the same number of
+  /// steps.
+  Reduce,
+}
+
+impl Pod {
+  /// Returns true if a term obeys a property under the time quota.
+  /// Terms that do not parse are an error.
+  pub fn check_property(
+    &mut self,
+    property: Property,
+    src: &str,
+    time_quota: u64) -> Result<bool> {
+    let root = parse(src, &mut self.heap)?;
+    let dst = match property {
+      Property::Quote => self.check_quote(root),
+      Property::Reduce => self.check_reduce(root, time_quota),
+    };
+    self.collect()?;
+    return dst;
+  }
+
+  fn check_quote(&mut self, root: Gc) -> Result<bool> {
+    let mut src = String::new();
+    quote(root, &self.heap, &mut src)?;
+    let copy = parse(&src, &mut self.heap)?;
+    return self.heap.equals(root, copy);
+  }
+
+  fn check_reduce(&mut self, root: Gc, time_quota: u64) -> Result<bool> {
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+      let mut quota = time_quota;
+      let target = reduce(
+        root, &mut self.heap, &self.tab, &self.rewrites, &self.host,
+        &mut quota);
+      runs.push((target, quota));
+    }
+    match (&runs[0], &runs[1]) {
+      (&(Ok(lhs), lhs_quota), &(Ok(rhs), rhs_quota)) => {
+        return Ok(lhs_quota == rhs_quota && self.heap.equals(lhs, rhs)?);
+      }
+      (&(ref lhs, _), &(ref rhs, _)) => {
+        return Ok(lhs.is_err() && lhs == rhs);
+      }
+    }
+  }
+
+  /// Checks `count` terms drawn from a generator against every
+  /// property, returning each term that fails one.
+  pub fn find_counterexamples(
+    &mut self,
+    generator: &mut Generator,
+    count: usize,
+    time_quota: u64) -> Result<Vec<(Property, String)>> {
+    let mut dst = Vec::new();
+    for _ in 0..count {
+      let src = generator.term();
+      for property in [Property::Quote, Property::Reduce].iter() {
+        if !self.check_property(*property, &src, time_quota)? {
+          dst.push((*property, src.clone()));
+        }
+      }
+    }
+    return Ok(dst);
+  }
+}
+
+#[test]
+fn property() {
+  let terms = vec!["a a", "a []", "[] a", "[] []", "[a]", "[[]]"];
+  assert_eq!(enumerate(&["a"], 2), terms);
+  assert_eq!(enumerate(&["a", "b"], 3).len(), 57);
+  let src = "```
+:swap f
+:loop [loop] a
++plus-zero $n [zero] plus => $n
+```
+";
+  let mut pod = Pod::from_string(src, 1 << 16, 1 << 12).unwrap();
+  for src in enumerate(&["a", "d", "f", "swap"], 3).iter() {
+    assert_eq!(pod.check_property(Property::Quote, src, 256), Ok(true));
+    assert_eq!(pod.check_property(Property::Reduce, src, 256), Ok(true));
+  }
+  let mut generator = Generator::new(7, 12);
+  let first = generator.term();
+  assert_eq!(Generator::new(7, 12).term(), first);
+  let spaced = first.replace('[', " [ ").replace(']', " ");
+  assert_eq!(spaced.split_whitespace().count(), 12);
+  let extra = [
+    "swap", "loop", "plus", "zero", "12", "\"a\\n\"", "(assert block)",
+  ];
+  generator.atoms.extend(extra.iter().map(|x| x.to_string()));
+  let found = pod.find_counterexamples(&mut generator, 256, 256);
+  assert_eq!(found, Ok(vec![]));
+}
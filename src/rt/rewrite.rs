@@ -0,0 +1,258 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use super::symbolic::is_variable;
+
+/// A rewrite rule declared as `+name pattern => replacement`. The
+/// pattern is a run of values ending in a word, its head. When the
+/// reducer reaches the head with values on top of the environment
+/// that match the rest, they are replaced by the replacement.
+#[derive(Debug, Clone)]
+pub(super) struct Rewrite {
+  pub(super) name: Rc<str>,
+  pub(super) head: Rc<str>,
+  /// The values matched, bottom first.
+  pattern: Gc,
+  replacement: Gc,
+}
+
+/// The rewrite rules of a pod, tried in the order they were declared.
+pub(super) type Rewrites = Vec<Rewrite>;
+
+/// Variables bound while matching a pattern.
+type Bindings = HashMap<Rc<str>, Gc>;
+
+/// Returns the name of a pattern variable, a word like `$x`. Words
+/// like `$2` are symbolic variables instead, and stay words.
+fn variable(heap: &Heap, pointer: Gc) -> Result<Option<Rc<str>>> {
+  if let &Object::Word(ref key) = heap.get_ref(pointer)? {
+    if key.len() > 1 && key.starts_with('$') && !is_variable(key) {
+      return Ok(Some(key.clone()));
+    }
+  }
+  return Ok(None);
+}
+
+/// Adds the variables of a term, inside blocks as well as out, to a
+/// set.
+fn variables(
+  heap: &Heap, root: Gc, dst: &mut HashSet<Rc<str>>) -> Result<()> {
+  for item in heap.get_items(root)?.into_iter() {
+    if let Some(name) = variable(heap, item)? {
+      dst.insert(name);
+    } else if let &Object::Block(body) = heap.get_ref(item)? {
+      variables(heap, body, dst)?;
+    }
+  }
+  return Ok(());
+}
+
+impl Rewrite {
+  /// Reads the term of a rule, split at its `=>`. The pattern must be
+  /// values or variables followed by a word, and the replacement may
+  /// only use variables the pattern binds.
+  pub(super) fn new(
+    heap: &mut Heap, name: Rc<str>, root: Gc) -> Result<Self> {
+    let items = heap.get_items(root)?;
+    let mut arrows = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+      if let &Object::Word(ref key) = heap.get_ref(*item)? {
+        if key.as_ref() == "=>" {
+          arrows.push(index);
+        }
+      }
+    }
+    let arrow = match arrows.as_slice() {
+      &[arrow] if arrow > 0 => arrow,
+      _ => {
+        return Err(Error::Syntax);
+      }
+    };
+    let head = match heap.get_ref(items[arrow - 1])? {
+      &Object::Word(ref key) if !key.starts_with('$') => key.clone(),
+      _ => {
+        return Err(Error::Syntax);
+      }
+    };
+    for item in items[..arrow - 1].iter() {
+      if variable(heap, *item)?.is_none() && !heap.is_value(*item)? {
+        return Err(Error::Syntax);
+      }
+    }
+    let pattern = heap.new_items(&items[..arrow - 1])?;
+    let replacement = heap.new_items(&items[arrow + 1..])?;
+    let mut bound = HashSet::new();
+    variables(heap, pattern, &mut bound)?;
+    let mut used = HashSet::new();
+    variables(heap, replacement, &mut used)?;
+    if !used.is_subset(&bound) {
+      return Err(Error::Syntax);
+    }
+    return Ok(Rewrite {
+      name: name,
+      head: head,
+      pattern: pattern,
+      replacement: replacement,
+    });
+  }
+
+  /// Marks the terms of the rule as reachable.
+  pub(super) fn mark(&self, heap: &mut Heap) -> Result<()> {
+    heap.mark(self.pattern)?;
+    return heap.mark(self.replacement);
+  }
+
+  /// Writes the rule as it would be declared.
+  pub(super) fn quote(&self, heap: &Heap, buf: &mut String) -> Result<()> {
+    buf.push('+');
+    buf.push_str(&self.name);
+    buf.push(' ');
+    if !heap.get_items(self.pattern)?.is_empty() {
+      quote(self.pattern, heap, buf)?;
+      buf.push(' ');
+    }
+    buf.push_str(&self.head);
+    buf.push_str(" =>");
+    if !heap.get_items(self.replacement)?.is_empty() {
+      buf.push(' ');
+      quote(self.replacement, heap, buf)?;
+    }
+    return Ok(());
+  }
+}
+
+/// Matches items against patterns one for one. A variable matches any
+/// item, and the same item wherever it appears again; a block matches
+/// a block whose body matches its own; anything else must be equal.
+fn matches(
+  heap: &Heap,
+  patterns: &[Gc],
+  items: &[Gc],
+  bindings: &mut Bindings) -> Result<bool> {
+  if patterns.len() != items.len() {
+    return Ok(false);
+  }
+  for (pattern, item) in patterns.iter().zip(items.iter()) {
+    if let Some(name) = variable(heap, *pattern)? {
+      if let Some(bound) = bindings.get(&name) {
+        if !heap.equals(*bound, *item)? {
+          return Ok(false);
+        }
+      } else {
+        bindings.insert(name, *item);
+      }
+      continue;
+    }
+    let is_match = match (heap.get_ref(*pattern)?, heap.get_ref(*item)?) {
+      (&Object::Block(lhs), &Object::Block(rhs)) => {
+        let lhs = heap.get_items(lhs)?;
+        let rhs = heap.get_items(rhs)?;
+        matches(heap, &lhs, &rhs, bindings)?
+      }
+      _ => heap.equals(*pattern, *item)?,
+    };
+    if !is_match {
+      return Ok(false);
+    }
+  }
+  return Ok(true);
+}
+
+/// Replaces the variables of a term with the items bound to them.
+fn substitute(heap: &mut Heap, root: Gc, bindings: &Bindings) -> Result<Gc> {
+  let mut items = Vec::new();
+  for item in heap.get_items(root)?.into_iter() {
+    if let Some(name) = variable(heap, item)? {
+      items.push(bindings[&name]);
+      continue;
+    }
+    match heap.get_ref(item)? {
+      &Object::Block(body) => {
+        let body = substitute(heap, body, bindings)?;
+        items.push(heap.new_block(body)?);
+      }
+      _ => {
+        items.push(item);
+      }
+    }
+  }
+  return heap.new_items(&items);
+}
+
+impl Thread {
+  /// Applies the first rule for a head word whose pattern matches the
+  /// top of the environment. Returns false if none does.
+  pub(super) fn rewrite(
+    &mut self,
+    head: &str,
+    heap: &mut Heap,
+    rewrites: &Rewrites) -> Result<bool> {
+    for rewrite in rewrites.iter().filter(|x| x.head.as_ref() == head) {
+      let patterns = heap.get_items(rewrite.pattern)?;
+      if self.frame.env.len() < patterns.len() {
+        continue;
+      }
+      let start = self.frame.env.len() - patterns.len();
+      let mut bindings = HashMap::new();
+      let values = &self.frame.env[start..];
+      if !matches(heap, &patterns, values, &mut bindings)? {
+        continue;
+      }
+      self.frame.env.truncate(start);
+      let target = substitute(heap, rewrite.replacement, &bindings)?;
+      self.push_continuation_front(target);
+      return Ok(true);
+    }
+    return Ok(false);
+  }
+}
+
+#[test]
+fn rewrite() {
+  let src = "```
+:swap f
++plus-zero $n [zero] plus => $n
++plus-succ $m [$n succ] plus => $m $n plus [succ] c
++twice [$x] [$x] same => [yes]
+```
+";
+  let mut pod = Pod::from_string(src, 4096, 4096).unwrap();
+  check_evals(&mut pod, 4096, &[
+    ("[A] [zero] plus", "[A]"),
+    ("[A] [[[zero] succ] succ] plus", "[A succ succ]"),
+    ("[B] [A] [A] same", "[B] [yes]"),
+    ("[A] [B] same", "[A] [B] same"),
+    ("[zero] plus", "[zero] plus"),
+    ("+known [$1] foo => [one]", "+known [$1] foo => [one]"),
+    ("[$1] foo", "[one]"),
+    ("[$2] foo", "[$2] foo"),
+    ("+swap-a [A] swap => [was-a]", "+swap-a [A] swap => [was-a]"),
+    ("[A] swap", "[was-a]"),
+    ("[B] swap", "[B] f"),
+    ("~+swap-a", "~+swap-a"),
+    ("[A] swap", "[A] f"),
+  ]);
+  assert_eq!(pod.eval("+bad $x foo => $y", 4096), Err(Error::Syntax));
+  assert_eq!(pod.eval("+bad $x f => $x", 4096), Err(Error::Syntax));
+  assert_eq!(pod.eval("+bad plus $x => $x", 4096), Err(Error::Syntax));
+  assert_eq!(pod.eval("+bad $1 foo => $1", 4096), Err(Error::Syntax));
+  assert!(pod.to_string().unwrap().contains(
+    "+plus-succ $m [$n succ] plus => $m $n plus [succ] c\n"));
+  assert!(pod.undo());
+  assert_eq!(pod.eval("[A] swap", 4096), Ok("[was-a]".to_string()));
+}
@@ -0,0 +1,219 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use super::arity::check_arity;
+use super::equal::reduce_fully;
+
+/// The most inputs a term is given before symbolic evaluation gives up.
+const MAX_INPUTS: usize = 8;
+
+lazy_static! {
+  static ref VARIABLE_REGEX: regex::Regex = {
+    regex::Regex::new(r"^\$([0-9]+)$").unwrap()
+  };
+}
+
+/// The general behaviour of a term: given values for the variables in
+/// `inputs`, bottom first, it leaves `output`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbolic {
+  pub inputs: Vec<String>,
+  pub output: String,
+}
+
+impl std::fmt::Display for Symbolic {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for input in self.inputs.iter() {
+      write!(f, "{} ", input)?;
+    }
+    return write!(f, "-- {}", self.output);
+  }
+}
+
+/// Returns true if a word is a variable, like `$2`. Rewrite rules
+/// leave these alone, so their own variables need a letter after `$`.
+pub(super) fn is_variable(key: &str) -> bool {
+  return VARIABLE_REGEX.is_match(key);
+}
+
+/// Returns the number of a variable word, like `$2`.
+fn variable(heap: &Heap, pointer: Gc) -> Result<Option<usize>> {
+  if let &Object::Word(ref key) = heap.get_ref(pointer)? {
+    if let Some(data) = VARIABLE_REGEX.captures(key) {
+      return Ok(data[1].parse().ok());
+    }
+  }
+  return Ok(None);
+}
+
+/// Replaces each variable written in a term with the value it names,
+/// a block holding the variable, and returns the highest one used.
+pub(super) fn bind(heap: &mut Heap, root: Gc, max: &mut usize) -> Result<Gc> {
+  let mut items = Vec::new();
+  for item in heap.get_items(root)?.into_iter() {
+    if let Some(number) = variable(heap, item)? {
+      *max = (*max).max(number);
+      items.push(heap.new_block(item)?);
+      continue;
+    }
+    match heap.get_ref(item)? {
+      &Object::Block(body) => {
+        let body = bind(heap, body, max)?;
+        items.push(heap.new_block(body)?);
+      }
+      _ => {
+        items.push(item);
+      }
+    }
+  }
+  return heap.new_items(&items);
+}
+
+/// Quotes a term, writing a block that holds only a variable as the
+/// variable itself, and a variable run in place as `$N a`.
+fn render(heap: &Heap, root: Gc, buf: &mut String) -> Result<()> {
+  for (index, item) in heap.get_items(root)?.into_iter().enumerate() {
+    if index > 0 {
+      buf.push(' ');
+    }
+    if variable(heap, item)?.is_some() {
+      quote(item, heap, buf)?;
+      buf.push_str(" a");
+      continue;
+    }
+    match heap.get_ref(item)? {
+      &Object::Block(body) => {
+        let body_items = heap.get_items(body)?;
+        let is_variable = body_items.len() == 1 &&
+          variable(heap, body_items[0])?.is_some();
+        if is_variable {
+          quote(body_items[0], heap, buf)?;
+        } else {
+          buf.push('[');
+          render(heap, body, buf)?;
+          buf.push(']');
+        }
+      }
+      _ => {
+        quote(item, heap, buf)?;
+      }
+    }
+  }
+  return Ok(());
+}
+
+/// Puts the values of some variables beneath a term, the first at the
+/// bottom.
+pub(super) fn with_variables(
+  heap: &mut Heap, root: Gc, numbers: &[usize]) -> Result<Gc> {
+  let mut items = Vec::new();
+  for number in numbers.iter() {
+    let word = heap.new_word(format!("${}", number).into())?;
+    items.push(heap.new_block(word)?);
+  }
+  items.push(root);
+  return heap.new_items(&items);
+}
+
+impl Pod {
+  /// Reduces a term over as many unknown inputs as it consumes, so the
+  /// result shows its general behaviour: `swap drop` leaves `$2` given
+  /// `$1 $2`. Variables may also be written in the term as `$N`, and
+  /// inputs are numbered after the highest of them. The
+  /// number of inputs is the least for which the term neither runs
+  /// out of values nor, given one more, touches the extra one.
+  pub fn symbolic(
+    &mut self, src: &str, time_quota: u64) -> Result<Symbolic> {
+    let root = parse(src, &mut self.heap)?;
+    let dst = self.symbolic_root(root, time_quota);
+    self.collect()?;
+    return dst;
+  }
+
+  /// Reduces a term over exactly `inputs` unknown inputs, as
+  /// `symbolic` does once it has settled how many a term takes. Terms
+  /// that take fewer leave the extra inputs in the output.
+  pub fn symbolic_over(
+    &mut self, src: &str, inputs: usize, time_quota: u64) -> Result<Symbolic> {
+    let root = parse(src, &mut self.heap)?;
+    let mut quota = time_quota;
+    let mut base = 0;
+    let root = bind(&mut self.heap, root, &mut base)?;
+    let numbers: Vec<usize> = (base + 1..base + inputs + 1).collect();
+    let source = with_variables(&mut self.heap, root, &numbers)?;
+    let dst = reduce_fully(self, source, &mut quota).and_then(|target| {
+      let mut output = String::new();
+      render(&self.heap, target, &mut output)?;
+      return Ok(Symbolic {
+        inputs: numbers.iter().map(|x| format!("${}", x)).collect(),
+        output: output,
+      });
+    });
+    self.collect()?;
+    return dst;
+  }
+
+  fn symbolic_root(
+    &mut self, root: Gc, time_quota: u64) -> Result<Symbolic> {
+    let mut quota = time_quota;
+    let mut base = 0;
+    let root = bind(&mut self.heap, root, &mut base)?;
+    for inputs in 0..MAX_INPUTS + 1 {
+      let underflows = check_arity(
+        &self.heap, &self.tab, &self.host, root, inputs)?;
+      if !underflows.is_empty() {
+        continue;
+      }
+      let numbers: Vec<usize> = (base + 1..base + inputs + 1).collect();
+      let source = with_variables(&mut self.heap, root, &numbers)?;
+      let target = reduce_fully(self, source, &mut quota)?;
+      let extra = base + inputs + 1;
+      let wider = with_variables(&mut self.heap, source, &[extra])?;
+      let wider = reduce_fully(self, wider, &mut quota)?;
+      let expected = with_variables(&mut self.heap, target, &[extra])?;
+      if !self.heap.equals(wider, expected)? {
+        continue;
+      }
+      let mut output = String::new();
+      render(&self.heap, target, &mut output)?;
+      return Ok(Symbolic {
+        inputs: numbers.iter().map(|x| format!("${}", x)).collect(),
+        output: output,
+      });
+    }
+    return Err(Error::Underflow);
+  }
+}
+
+#[test]
+fn symbolic() {
+  let src = "```\n:swap f\n:drop e\n:pair b f b f c\n```\n";
+  let mut pod = Pod::from_string(src, 4096, 4096).unwrap();
+  let mut check = |source, expected| {
+    let dst = pod.symbolic(source, 4096).unwrap();
+    assert_eq!(dst.to_string(), expected);
+  };
+  check("swap drop", "$1 $2 -- $2");
+  check("[A]", "-- [A]");
+  check("pair", "$1 $2 -- [$1 $2]");
+  check("d", "$1 -- $1 $1");
+  check("a", "$1 -- $1 a");
+  check("$1 $2 swap", "-- $2 $1");
+  check("$1 swap", "$2 -- $1 $2");
+  assert_eq!(pod.symbolic("[d a] d a", 4096), Err(Error::Time));
+}
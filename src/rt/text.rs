@@ -0,0 +1,120 @@
+// This file is a part of Sundial.
+// Copyright (C) 2018 Matthew Blount
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Affero General Public License for more details.
+
+// You should have received a copy of the GNU Affero General Public
+// License along with this program.  If not, see
+// <https://www.gnu.org/licenses/.
+
+use super::*;
+use std::cmp::Ordering;
+
+/// Binds the natives on text and byte strings.
+pub(super) fn define_natives(natives: &mut HashMap<Rc<str>, Native>) {
+  let functions: Vec<(&str, usize, Function)> = vec![
+    ("length", 1, Rc::new(length)),
+    ("slice", 3, Rc::new(slice)),
+    ("compare", 2, Rc::new(compare)),
+  ];
+  for (key, arity, function) in functions.into_iter() {
+    let native = Native {
+      arity: arity,
+      function: function,
+    };
+    natives.insert(key.into(), native);
+  }
+}
+
+/// Counts the characters of a text or the bytes of a byte string.
+fn length(args: &[Value]) -> Result<Vec<Value>> {
+  match &args[0] {
+    &Value::Text(ref value) => {
+      return Ok(vec![Value::Number(value.chars().count() as u64)]);
+    }
+    &Value::Bytes(ref value) => {
+      return Ok(vec![Value::Number(value.len() as u64)]);
+    }
+    _ => {
+      return Err(Error::Tag);
+    }
+  }
+}
+
+/// Takes a text or byte string and two numbers, and leaves the part
+/// from the first up to the second, counted as `length` counts. Bounds
+/// past the end stop at the end.
+fn slice(args: &[Value]) -> Result<Vec<Value>> {
+  let (start, end) = match (&args[1], &args[2]) {
+    (&Value::Number(start), &Value::Number(end)) => (start, end.max(start)),
+    _ => {
+      return Err(Error::Tag);
+    }
+  };
+  match &args[0] {
+    &Value::Text(ref value) => {
+      let value: String = value.chars()
+        .skip(start as usize).take((end - start) as usize).collect();
+      return Ok(vec![Value::Text(value.into())]);
+    }
+    &Value::Bytes(ref value) => {
+      let length = value.len() as u64;
+      let range = start.min(length) as usize..end.min(length) as usize;
+      return Ok(vec![Value::Bytes(value[range].into())]);
+    }
+    _ => {
+      return Err(Error::Tag);
+    }
+  }
+}
+
+/// Orders two texts or two byte strings. It leaves a block that, run
+/// above three blocks for less, equal and greater, keeps the one that
+/// holds.
+fn compare(args: &[Value]) -> Result<Vec<Value>> {
+  let order = match (&args[0], &args[1]) {
+    (&Value::Text(ref lhs), &Value::Text(ref rhs)) => lhs.cmp(rhs),
+    (&Value::Bytes(ref lhs), &Value::Bytes(ref rhs)) => lhs.cmp(rhs),
+    _ => {
+      return Err(Error::Tag);
+    }
+  };
+  let code = match order {
+    Ordering::Less => "[e e]",
+    Ordering::Equal => "[e f e]",
+    Ordering::Greater => "[f e f e]",
+  };
+  return Ok(vec![Value::Code(code.into())]);
+}
+
+#[test]
+fn text() {
+  let mut pod = Pod::from_string("", 1024, 1024).unwrap();
+  check_evals(&mut pod, 1024, &[
+    ("\"h\u{e9}llo\" length", "5"),
+    ("b\"\\x00ab\" length", "3"),
+    ("\"h\u{e9}llo\" 1 3 slice", "\"\u{e9}l\""),
+    ("\"hello\" 3 9 slice", "\"lo\""),
+    ("\"hello\" 4 2 slice", "\"\""),
+    ("b\"abc\" 1 2 slice", "b\"b\""),
+    ("b\"abc\" 5 9 slice", "b\"\""),
+    ("[lt] [eq] [gt] \"a\" \"b\" compare a", "[lt]"),
+    ("[lt] [eq] [gt] \"b\" \"b\" compare a", "[eq]"),
+    ("[lt] [eq] [gt] b\"b\" b\"a\" compare a", "[gt]"),
+    ("\"ab\" \"cd\" c length", "4"),
+    ("2 slice", "2 slice"),
+    ("[A] length", "[A] length"),
+    ("\"a\" b\"a\" compare", "\"a\" b\"a\" compare"),
+    ("\"a\" [A] 1 slice", "\"a\" [A] 1 slice"),
+    (":length d", ":length d"),
+    ("\"a\" length", "\"a\" \"a\""),
+  ]);
+}